@@ -13,10 +13,16 @@ pub mod zeuslib {
     pub type Backend = TermionBackend<AlternateScreen<MouseTerminal<termion::raw::RawTerminal<std::io::Stdout>>>>;
 
     pub mod actions;
+    pub mod commands;
     pub mod ui;
     pub mod state;
     pub mod input;
     pub mod events;
     pub mod config;
     pub mod utils;
+    pub mod jobs;
+    pub mod finder;
+    pub mod listing;
+    pub mod watcher;
+    pub mod external;
 } /* zeuslib */
@@ -1,4 +1,5 @@
 extern crate termion;
+use std::path::{Path, PathBuf};
 use std::{error::Error, io};
 use termion::{input::MouseTerminal, raw::IntoRawMode, screen::AlternateScreen};
 use tui::backend::TermionBackend;
@@ -7,38 +8,124 @@ use tui::Terminal;
 
 use zeus_fm::zeuslib::config::Config;
 use zeus_fm::zeuslib::events::loopaction::EventLoopAction;
-use zeus_fm::zeuslib::events::procevent::{handle_input, handle_tick};
+use zeus_fm::zeuslib::events::procevent::{
+    handle_find_event, handle_input, handle_job_event, handle_list_event, handle_tick,
+    handle_watch_event, key_deadline,
+};
 use zeus_fm::zeuslib::events::{Event, Events};
+use zeus_fm::zeuslib::external::Suspender;
 use zeus_fm::zeuslib::state::State;
+use zeus_fm::zeuslib::ui::dialog::Dialog;
 use zeus_fm::zeuslib::ui::draw;
 use zeus_fm::zeuslib::utils::fs::*;
 
+/// The config files to load over the defaults, in order: the user's file,
+/// then the project's in the working directory. Each comes with whether it
+/// may define openers; only the user's own file may, see
+/// `Config::load_project_file`.
+fn config_layers(cfg_path: Option<&Path>) -> Vec<(PathBuf, bool)> {
+    let mut layers: Vec<(PathBuf, bool)> = Vec::new();
+    if let Some(cfg_path) = cfg_path.filter(|x| x.is_file()) {
+        layers.push((cfg_path.to_path_buf(), true));
+    }
+    if let Ok(dir) = std::env::current_dir() {
+        let local = dir.join(LOCAL_CONFIG_FILE);
+        if local.is_file() && Some(local.as_path()) != cfg_path {
+            layers.push((local, false));
+        }
+    }
+    layers
+}
+
+/// Applies one layer to `config`. A file that fails to load is left out,
+/// but the problem is recorded.
+fn load_layer(config: &mut Config, path: &Path, trusted: bool) {
+    let loaded = if trusted {
+        config.load_file(path)
+    } else {
+        config.load_project_file(path)
+    };
+    if let Err(e) = loaded {
+        config.errors.push(e);
+    }
+}
+
+/// Loads and validates the config file at `path`, or every file that would
+/// be loaded without one, printing every problem under its file. Returns
+/// whether they are free of problems.
+fn check_config(path: Option<&Path>) -> bool {
+    let layers = match path {
+        Some(path) if !path.is_file() => {
+            eprintln!("{}: no such file", path.display());
+            return false;
+        }
+        Some(path) => {
+            let trusted = path.file_name() != Some(LOCAL_CONFIG_FILE.as_ref());
+            vec![(path.to_path_buf(), trusted)]
+        }
+        None => config_layers((*CONFIG_FILE).as_deref()),
+    };
+    if layers.is_empty() {
+        println!("No config files, using defaults");
+        return true;
+    }
+    let mut config = Config::default();
+    let mut ok = true;
+    for (path, trusted) in layers.iter() {
+        let before = config.errors.len();
+        load_layer(&mut config, path, *trusted);
+        if config.errors.len() == before {
+            println!("{}: OK", path.display());
+        }
+        for e in config.errors[before..].iter() {
+            eprintln!("{}", e);
+            ok = false;
+        }
+    }
+    ok
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(i) = args.iter().position(|x| x == "--check-config") {
+        let path = args.get(i + 1).map(PathBuf::from);
+        let ok = check_config(path.as_deref());
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
     let cfg_path = &*CONFIG_FILE;
     let cfg_dir = &*CONFIG_DIR;
     if let Some(cfg_dir) = cfg_dir {
         if !cfg_dir.is_dir() {
-            std::fs::create_dir_all(cfg_dir.to_owned())?;
+            std::fs::create_dir_all(cfg_dir)?;
         }
     }
-    let config: Config;
+    // Built-in defaults, then the user's config, then the project's
     if let Some(cfg_path) = cfg_path {
         if !cfg_path.is_file() {
-            std::fs::write(cfg_path.to_owned(), "")?;
-        }
-        config = if let Some(cfg) = Config::from_file(cfg_path) {
-            cfg
-        } else {
-            Config::default()
+            std::fs::write(cfg_path, "")?;
         }
-    } else {
-        config = Config::default();
+    }
+    let mut config = Config::default();
+    for (path, trusted) in config_layers(cfg_path.as_deref()) {
+        load_layer(&mut config, &path, trusted);
     }
 
-    let mut state = State::default();
+    let mut state = State {
+        openers: config.openers.clone(),
+        options: config.options.clone(),
+        ..Default::default()
+    };
+    state.apply_options();
+    if !config.errors.is_empty() {
+        state.message = format!("{} problem(s) in config", config.errors.len());
+        let lines = config.errors.iter().map(|e| e.to_string()).collect();
+        state.dialog = Some(Dialog::message("Config problems", lines));
+    }
 
 
     // Terminal initialization
+    let suspender = Suspender::new()?;
     let stdout = io::stdout().into_raw_mode()?;
     let stdout = MouseTerminal::from(stdout);
     let stdout = AlternateScreen::from(stdout);
@@ -46,19 +133,57 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut terminal = Terminal::new(backend)?;
 
     // Setup event handlers
-    let events = Events::new();
+    let mut events = Events::new();
+    // Quitting goes through the key map; the input thread must keep running
+    // while dialogs and external programs read `q`.
+    events.disable_exit_key();
+    state.jobs.set_sender(events.sender());
+    state.finder.set_sender(events.sender());
+    state.set_list_sender(events.sender());
+    if let Err(e) = state.watcher.set_sender(events.sender()) {
+        state.message = format!("Cannot watch directories for changes: {}", e);
+    }
     terminal.clear()?;
 
     loop {
-        draw(&mut terminal, &mut state)?;
-        let evt = events.next()?;
+        state.watch_dirs();
+        draw(&mut terminal, &mut state, &config)?;
+        // A pending key sequence times out on time, not on the next tick
+        let evt = events.next_until(key_deadline(&state))?;
         match evt {
-            Event::Input(input) => match handle_input(&mut state, &config, input) {
-                EventLoopAction::QuitLoop => break,
-                _ => {}
-            },
+            Event::Input(input) => {
+                if handle_input(&mut state, &mut config, input) == EventLoopAction::QuitLoop {
+                    break;
+                }
+            }
             Event::Tick => {
-                handle_tick(&mut state);
+                if handle_tick(&mut state, &config) == EventLoopAction::QuitLoop {
+                    break;
+                }
+            }
+            Event::Job(evt) => {
+                handle_job_event(&mut state, evt);
+            }
+            Event::Find(evt) => {
+                handle_find_event(&mut state, evt);
+            }
+            Event::List(evt) => {
+                handle_list_event(&mut state, evt);
+            }
+            Event::Watch(evt) => {
+                handle_watch_event(&mut state, evt);
+            }
+        }
+        if let Some(cmd) = state.external_command.take() {
+            let status = suspender.run(&mut terminal, &events, &cmd);
+            // The program may well have changed the files being shown
+            state.refresh();
+            if let Some(on_exit) = &cmd.on_exit {
+                if on_exit(&mut state, status) == EventLoopAction::QuitLoop {
+                    break;
+                }
+            } else if let Err(e) = status {
+                state.message = format!("Cannot run {}: {}", cmd.program.to_string_lossy(), e);
             }
         }
     }
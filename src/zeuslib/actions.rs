@@ -1,86 +1,1143 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use crate::zeuslib::events::loopaction::EventLoopAction;
-use crate::zeuslib::state::State;
+use crate::zeuslib::config::{openers_for, Opener};
+use crate::zeuslib::external::{editor, pager, shell, spawn_detached, ExternalCommand};
+use crate::zeuslib::jobs::JobOp;
+use crate::zeuslib::state::{RegisterMode, State};
+use crate::zeuslib::ui::dialog::{Dialog, DialogKind};
+use crate::zeuslib::ui::filelist::{FileList, SortKey};
+use crate::zeuslib::ui::finder::FinderView;
+use crate::zeuslib::ui::help::HelpView;
+use crate::zeuslib::utils::fs::{expand_tilde, private_temp_dir};
+use crate::zeuslib::utils::fs::mime::sniff_mime;
+use crate::zeuslib::utils::fs::rename::{plan_renames, RenamePlan};
+use crate::zeuslib::utils::fs::trash;
 
-fn quit_action(_state: &mut State) -> EventLoopAction {
+fn quit_action(_state: &mut State, _args: &ActionArgs) -> EventLoopAction {
     EventLoopAction::QuitLoop
 }
 
-fn next_tab_action(state: &mut State) -> EventLoopAction {
-    state.next_tab();
+fn next_tab_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    // Going round all the tabs changes nothing
+    for _ in 0..args.repeat() % state.tabs.len().max(1) {
+        state.next_tab();
+    }
     EventLoopAction::ContinueLoop
 }
 
-fn new_tab_action(state: &mut State) -> EventLoopAction {
+fn new_tab_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
     state.new_tab();
     EventLoopAction::ContinueLoop
 }
 
-fn move_down_action(state: &mut State) -> EventLoopAction {
-    let panel = state.get_current_panel_mut();
-    if let Ok(Some(panel)) = panel {
-        let mut panel = panel.borrow_mut();
-        panel.next();
-    }
-    {
-        let tab = state.get_current_tab_mut();
-        tab.update_preview();
+/// Runs `f` on the current panel's file list, then updates the preview for
+/// the entry the cursor ended up on.
+fn move_in_panel<F>(state: &mut State, f: F) -> EventLoopAction
+where
+    F: FnOnce(&mut FileList),
+{
+    if let Ok(Some(panel)) = state.get_current_panel_mut() {
+        f(&mut panel.borrow_mut());
     }
+    state.get_current_tab_mut().update_preview();
     EventLoopAction::ContinueLoop
 }
 
-fn move_up_action(state: &mut State) -> EventLoopAction {
-    let panel = state.get_current_panel_mut();
-    if let Ok(Some(panel)) = panel {
-        let mut panel = panel.borrow_mut();
-        panel.previous();
-    }
-    {
-        let tab = state.get_current_tab_mut();
-        tab.update_preview();
-    }
-    EventLoopAction::ContinueLoop
+/// Without a count `j` and `k` move by one item, wrapping around if
+/// `wrapscroll` is set; a count moves that many items.
+fn move_down_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let count = args.count;
+    move_in_panel(state, |list| match count {
+        Some(n) => list.move_by(n as isize),
+        None => list.next(),
+    })
+}
+
+fn move_up_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let count = args.count;
+    move_in_panel(state, |list| match count {
+        Some(n) => list.move_by(-(n as isize)),
+        None => list.previous(),
+    })
+}
+
+fn page_down_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let n = args.repeat();
+    move_in_panel(state, |list| list.page_down(n))
+}
+
+fn page_up_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let n = args.repeat();
+    move_in_panel(state, |list| list.page_up(n))
 }
 
-fn mark_action(state: &mut State) -> EventLoopAction {
-    let selected = { state.selected() };
-    let panel = state.get_current_panel_mut();
-    if let Some(i) = selected {
-        if let Ok(Some(panel)) = panel {
-            let mut panel = panel.borrow_mut();
-            let items = &mut panel.items;
-            items[i].marked = !items[i].marked;
-            panel.next();
+fn half_page_down_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let n = args.repeat();
+    move_in_panel(state, |list| list.half_page_down(n))
+}
+
+fn half_page_up_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let n = args.repeat();
+    move_in_panel(state, |list| list.half_page_up(n))
+}
+
+/// With a count, goes to that entry, counting from 1.
+fn go_top_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let count = args.count;
+    move_in_panel(state, |list| match count {
+        Some(n) => list.select(n.saturating_sub(1)),
+        None => list.go_top(),
+    })
+}
+
+fn go_bottom_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let count = args.count;
+    move_in_panel(state, |list| match count {
+        Some(n) => list.select(n.saturating_sub(1)),
+        None => list.go_bottom(),
+    })
+}
+
+fn go_middle_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    move_in_panel(state, |list| list.go_middle())
+}
+
+fn mark_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let len = match state.get_current_panel() {
+        Ok(Some(panel)) => panel.borrow().items.len(),
+        _ => 0,
+    };
+    for _ in 0..args.repeat().min(len) {
+        let selected = { state.selected() };
+        let panel = state.get_current_panel_mut();
+        if let Some(i) = selected {
+            if let Ok(Some(panel)) = panel {
+                let mut panel = panel.borrow_mut();
+                let items = &mut panel.items;
+                items[i].marked = !items[i].marked;
+                panel.next();
+            }
         }
     }
     EventLoopAction::ContinueLoop
 }
-fn cd_parent_action(state: &mut State) -> EventLoopAction {
+fn cd_parent_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
     let tab = state.get_current_tab_mut();
     tab.cd_parent();
     tab.update_preview();
     EventLoopAction::ContinueLoop
 }
-fn cd_selected_action(state: &mut State) -> EventLoopAction {
+fn cd_selected_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    let selected_is_file = match state.get_current_panel() {
+        Ok(Some(panel)) => panel.borrow().selected_item().map(|x| !x.is_dir()),
+        _ => None,
+    };
+    if selected_is_file == Some(true) {
+        return open_selected(state, false);
+    }
     let tab = state.get_current_tab_mut();
     tab.cd_selected();
     tab.update_preview();
     EventLoopAction::ContinueLoop
 }
 
-pub type Action = Rc<dyn Fn(&mut State) -> EventLoopAction>;
+/// The files an action works on: with a count, the selected entry and the
+/// ones after it, as in `3dd`; otherwise the marked files or the selected one.
+fn target_paths(state: &State, args: &ActionArgs) -> Vec<PathBuf> {
+    match args.count {
+        Some(n) => state.paths_from_selected(n),
+        None => state.target_paths(),
+    }
+}
+
+fn fill_register(state: &mut State, args: &ActionArgs, mode: RegisterMode) {
+    let paths = target_paths(state, args);
+    if paths.is_empty() {
+        return;
+    }
+    let verb = if mode == RegisterMode::Copy { "Yanked" } else { "Cut" };
+    state.message = format!("{} {} item(s)", verb, paths.len());
+    state.register.set(paths, mode);
+    state.clear_marks();
+}
+fn yank_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    fill_register(state, args, RegisterMode::Copy);
+    EventLoopAction::ContinueLoop
+}
+fn cut_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    fill_register(state, args, RegisterMode::Move);
+    EventLoopAction::ContinueLoop
+}
+fn paste_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    let dir = match &state.get_current_tab().dir {
+        Some(dir) => dir.clone(),
+        None => return EventLoopAction::ContinueLoop,
+    };
+    if state.register.is_empty() {
+        state.message = String::from("Nothing to paste");
+        return EventLoopAction::ContinueLoop;
+    }
+    let op = match state.register.mode {
+        RegisterMode::Copy => JobOp::Copy,
+        RegisterMode::Move => JobOp::Move,
+    };
+    // A cut register is cleared once the move has succeeded, see
+    // `handle_job_event`
+    let paths = state.register.paths.clone();
+    state.jobs.spawn(op, paths, Some(dir));
+    EventLoopAction::ContinueLoop
+}
+fn toggle_jobs_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    state.show_jobs = !state.show_jobs;
+    EventLoopAction::ContinueLoop
+}
+fn next_job_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    state.jobs.select_next();
+    EventLoopAction::ContinueLoop
+}
+fn pause_job_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    state.jobs.toggle_pause();
+    EventLoopAction::ContinueLoop
+}
+fn cancel_job_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    state.jobs.cancel();
+    EventLoopAction::ContinueLoop
+}
+
+fn trash_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let paths = target_paths(state, args);
+    if !paths.is_empty() {
+        state.jobs.spawn(JobOp::Trash, paths, None);
+    }
+    EventLoopAction::ContinueLoop
+}
+fn restore_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    let paths: Vec<PathBuf> = state
+        .target_paths()
+        .into_iter()
+        .filter(|p| trash::is_in_trash(p))
+        .collect();
+    if paths.is_empty() {
+        state.message = String::from("Nothing to restore here");
+        return EventLoopAction::ContinueLoop;
+    }
+    state.jobs.spawn(JobOp::Restore, paths, None);
+    EventLoopAction::ContinueLoop
+}
+fn show_trash_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    if let Some(dir) = trash::trash_files_dir() {
+        if std::fs::create_dir_all(&dir).is_ok() {
+            let tab = state.get_current_tab_mut();
+            tab.cd(Some(dir));
+            tab.update_preview();
+        }
+    }
+    EventLoopAction::ContinueLoop
+}
+fn delete_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let paths = target_paths(state, args);
+    if paths.is_empty() {
+        return EventLoopAction::ContinueLoop;
+    }
+    let title = format!("Permanently delete {} item(s)?", paths.len());
+    state.dialog = Some(Dialog::confirm(
+        &title,
+        Rc::new(move |state: &mut State| {
+            state.jobs.spawn(JobOp::Delete, paths.clone(), None);
+            EventLoopAction::ContinueLoop
+        }),
+    ));
+    EventLoopAction::ContinueLoop
+}
+const MKDIR_PARAMS: [Param; 1] = [param("names", ParamKind::List)];
+const CD_PARAMS: [Param; 1] = [param("path", ParamKind::Path)];
+const COMMAND_LINE_PARAMS: [Param; 1] = [param("text", ParamKind::List)];
+const FILTER_PARAMS: [Param; 1] = [param("pattern", ParamKind::List)];
+const SET_PARAMS: [Param; 1] = [param("options", ParamKind::List)];
+const SORT_PARAMS: [Param; 2] = [
+    param("by", ParamKind::Choice(&["name", "size", "mtime", "ext"])),
+    param("reverse", ParamKind::Bool),
+];
+
+fn make_dir(state: &mut State, name: &str) {
+    if let Some(dir) = state.get_current_tab().dir.clone() {
+        state.message = match std::fs::create_dir_all(dir.join(name)) {
+            Ok(()) => format!("Created {}", name),
+            Err(e) => format!("Cannot create {}: {}", name, e),
+        };
+        state.refresh();
+    }
+}
+fn mkdir_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    if !args.list("names").is_empty() {
+        for name in args.list("names") {
+            make_dir(state, name);
+        }
+        return EventLoopAction::ContinueLoop;
+    }
+    state.dialog = Some(Dialog::input(
+        "New directory",
+        "",
+        Rc::new(|state: &mut State, name: String| {
+            if !name.is_empty() {
+                make_dir(state, &name);
+            }
+            EventLoopAction::ContinueLoop
+        }),
+    ));
+    EventLoopAction::ContinueLoop
+}
+/// Changes the current tab to `path`, which may start with `~` and is
+/// relative to the current directory.
+fn change_dir(state: &mut State, path: &str) {
+    let path = expand_tilde(path.trim());
+    let path = match &state.get_current_tab().dir {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path,
+    };
+    match path.canonicalize() {
+        Ok(path) if path.is_dir() => {
+            let tab = state.get_current_tab_mut();
+            tab.cd(Some(path));
+            tab.update_preview();
+        }
+        _ => state.message = format!("Not a directory: {}", path.display()),
+    }
+}
+fn goto_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    let initial = match &state.get_current_tab().dir {
+        Some(dir) => format!("{}/", dir.display()),
+        None => String::new(),
+    };
+    state.dialog = Some(Dialog::input(
+        "Go to",
+        &initial,
+        Rc::new(|state: &mut State, path: String| {
+            change_dir(state, &path);
+            EventLoopAction::ContinueLoop
+        }),
+    ));
+    EventLoopAction::ContinueLoop
+}
+fn cd_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    change_dir(state, args.str("path").unwrap_or("~"));
+    EventLoopAction::ContinueLoop
+}
+fn command_line_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    state.cmdline.open(&args.list("text").join(" "));
+    EventLoopAction::ContinueLoop
+}
+fn search_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    state.search_origin = state.selected();
+    state.search_line.open("");
+    EventLoopAction::ContinueLoop
+}
+/// Jumps to the `count`th next match of the last search, down or up.
+fn repeat_search(state: &mut State, args: &ActionArgs, forward: bool) {
+    let matcher = match &state.search {
+        Some(matcher) => matcher.clone(),
+        None => {
+            state.message = String::from("No previous search");
+            return;
+        }
+    };
+    // Matches come round again after the last one
+    let matches = match state.get_current_panel() {
+        Ok(Some(panel)) => {
+            let panel = panel.borrow();
+            panel.items.iter().filter(|x| matcher.is_match(&x.name())).count()
+        }
+        _ => 0,
+    };
+    let n = (args.repeat() - 1) % matches.max(1) + 1;
+    for _ in 0..n {
+        let from = state.selected().unwrap_or(0);
+        if !state.jump_to_match(&matcher, from, forward, false) {
+            state.message = format!("Pattern not found: {}", matcher.as_str());
+            return;
+        }
+    }
+    state.set_highlight(Some(&matcher));
+}
+fn search_next_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    repeat_search(state, args, true);
+    EventLoopAction::ContinueLoop
+}
+fn search_prev_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    repeat_search(state, args, false);
+    EventLoopAction::ContinueLoop
+}
+/// Filters the listing by the pattern given, or opens a prompt that filters
+/// it as the pattern is typed.
+fn filter_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    let pattern = args.list("pattern").join(" ");
+    if !pattern.is_empty() {
+        match state.options.filter_matcher(&pattern) {
+            Ok(matcher) => state.get_current_tab_mut().set_filter(Some(matcher)),
+            Err(e) => state.message = e,
+        }
+        return EventLoopAction::ContinueLoop;
+    }
+    let current = state.get_current_tab().filter().cloned();
+    let text = current.as_ref().map(|x| x.as_str()).unwrap_or("");
+    state.filter_line.open(text);
+    state.filter_origin = current;
+    EventLoopAction::ContinueLoop
+}
+fn clear_filter_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    state.get_current_tab_mut().set_filter(None);
+    EventLoopAction::ContinueLoop
+}
+/// Opens the fuzzy finder over everything below the current directory.
+fn find_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    let root = match &state.get_current_tab().dir {
+        Some(dir) => dir.clone(),
+        None => return EventLoopAction::ContinueLoop,
+    };
+    state.finder.start(root.clone(), state.options.walk_options());
+    let options = &state.options;
+    state.find = Some(FinderView::new(&root, options.ignore_case, options.smart_case));
+    EventLoopAction::ContinueLoop
+}
+fn help_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    state.help = Some(HelpView::default());
+    EventLoopAction::ContinueLoop
+}
+fn set_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    if args.list("options").is_empty() {
+        state.message = state.options.describe();
+        return EventLoopAction::ContinueLoop;
+    }
+    for expr in args.list("options") {
+        if let Err(e) = state.options.set(expr) {
+            state.message = e;
+            break;
+        }
+    }
+    state.apply_options();
+    EventLoopAction::ContinueLoop
+}
+fn sort_action(state: &mut State, args: &ActionArgs) -> EventLoopAction {
+    // The parameter only accepts valid keys
+    match args.str("by").and_then(SortKey::parse) {
+        Some(key) => {
+            state.options.list.sort = key;
+            state.options.list.sort_reverse = args.bool("reverse");
+            state.apply_options();
+        }
+        None => state.message = format!("Sorted by {}", state.options.list.sort.as_str()),
+    }
+    EventLoopAction::ContinueLoop
+}
+fn rename_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    let path = match state.get_current_panel() {
+        Ok(Some(panel)) => match panel.borrow().selected_item() {
+            Some(item) => item.path,
+            None => return EventLoopAction::ContinueLoop,
+        },
+        _ => return EventLoopAction::ContinueLoop,
+    };
+    let file_name = path.file_name().unwrap_or_default();
+    let name = file_name.to_string_lossy().into_owned();
+    if file_name.to_str().is_none() {
+        // The bytes that are not UTF-8 cannot be edited, only replaced
+        state.message = String::from("Name is not UTF-8; renaming replaces the bytes shown as �");
+    }
+    let mut dialog = Dialog::input(
+        "Rename",
+        &name,
+        Rc::new(move |state: &mut State, new_name: String| {
+            if new_name.is_empty() || new_name.contains('/') {
+                state.message = format!("Invalid name: '{}'", new_name);
+                return EventLoopAction::ContinueLoop;
+            }
+            let dir = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+            let new_path = dir.join(&new_name);
+            if new_path == path {
+                return EventLoopAction::ContinueLoop;
+            }
+            if std::fs::symlink_metadata(&new_path).is_ok() {
+                state.message = format!("'{}' already exists", new_name);
+                return EventLoopAction::ContinueLoop;
+            }
+            match std::fs::rename(&path, &new_path) {
+                Ok(()) => {
+                    state.message = format!("Renamed to {}", new_name);
+                    let tab = state.get_current_tab_mut();
+                    tab.cd(Some(dir));
+                    tab.select_path(&new_path);
+                }
+                Err(e) => state.message = format!("Rename failed: {}", e),
+            }
+            EventLoopAction::ContinueLoop
+        }),
+    );
+    // Start with the cursor in front of the extension, as most renames keep it
+    if let DialogKind::Input { input, .. } = &mut dialog.kind {
+        if let Some(dot) = name.rfind('.').filter(|i| *i > 0) {
+            input.set_cursor(name[..dot].chars().count());
+        }
+    }
+    state.dialog = Some(dialog);
+    EventLoopAction::ContinueLoop
+}
+fn apply_rename_plan(state: &mut State, plan: RenamePlan) {
+    let count = plan.renames.len();
+    state.message = match plan.apply() {
+        Ok(()) => format!("Renamed {} item(s)", count),
+        Err(e) => format!("Bulk rename failed: {}", e),
+    };
+    let tab = state.get_current_tab_mut();
+    tab.cd(Some(plan.dir.clone()));
+    tab.update_preview();
+}
+fn bulk_rename_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    let dir = match state.get_current_tab().dir.clone() {
+        Some(dir) => dir,
+        None => return EventLoopAction::ContinueLoop,
+    };
+    let names: Vec<PathBuf> = state
+        .target_paths()
+        .iter()
+        .filter_map(|p| p.file_name().map(PathBuf::from))
+        .collect();
+    // A name that is not UTF-8 could not be written back from the editor
+    let old: Vec<String> = names
+        .iter()
+        .filter_map(|n| n.to_str().map(String::from))
+        .collect();
+    if old.len() < names.len() {
+        state.message = format!(
+            "Left out {} name(s) that are not UTF-8; use rename for those",
+            names.len() - old.len()
+        );
+    }
+    if old.is_empty() {
+        return EventLoopAction::ContinueLoop;
+    }
+    let mut contents = old.join("\n");
+    contents.push('\n');
+    // A private directory, so that nobody else can swap the file for a link
+    let tmp_dir = match private_temp_dir("zeus-bulk-rename-") {
+        Ok(dir) => dir,
+        Err(e) => {
+            state.message = format!("Cannot create a temporary directory: {}", e);
+            return EventLoopAction::ContinueLoop;
+        }
+    };
+    let tmp = tmp_dir.join("names.txt");
+    let written = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(contents.as_bytes()));
+    if let Err(e) = written {
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+        state.message = format!("Cannot write {}: {}", tmp.display(), e);
+        return EventLoopAction::ContinueLoop;
+    }
+
+    let cmd = ExternalCommand::new(editor())
+        .arg(&tmp)
+        .current_dir(Some(dir.clone()))
+        .on_exit(Rc::new(move |state: &mut State, status| {
+            let edited = std::fs::read_to_string(&tmp);
+            let _ = std::fs::remove_dir_all(&tmp_dir);
+            match status {
+                Ok(status) if status.success() => {}
+                Ok(_) => {
+                    state.message = String::from("Editor failed, nothing renamed");
+                    return EventLoopAction::ContinueLoop;
+                }
+                Err(e) => {
+                    state.message = format!("Cannot run editor: {}", e);
+                    return EventLoopAction::ContinueLoop;
+                }
+            }
+            let new: Vec<String> = match edited {
+                Ok(s) => s.lines().map(String::from).collect(),
+                Err(e) => {
+                    state.message = format!("Cannot read back names: {}", e);
+                    return EventLoopAction::ContinueLoop;
+                }
+            };
+            let plan = match plan_renames(&dir, &old, &new) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    state.message = format!("Bulk rename aborted: {}", e);
+                    return EventLoopAction::ContinueLoop;
+                }
+            };
+            if plan.is_empty() {
+                state.message = String::from("No names changed");
+                return EventLoopAction::ContinueLoop;
+            }
+            let mut details = plan.preview();
+            if plan.has_cycle {
+                details.push(String::from("(names are swapped; temporary names will be used)"));
+            }
+            let title = format!("Rename {} item(s)?", plan.renames.len());
+            let plan = RefCell::new(Some(plan));
+            state.dialog = Some(
+                Dialog::confirm(
+                    &title,
+                    Rc::new(move |state: &mut State| {
+                        if let Some(plan) = plan.borrow_mut().take() {
+                            apply_rename_plan(state, plan);
+                        }
+                        EventLoopAction::ContinueLoop
+                    }),
+                )
+                .with_details(details),
+            );
+            EventLoopAction::ContinueLoop
+        }));
+    state.external_command = Some(cmd);
+    EventLoopAction::ContinueLoop
+}
+fn shell_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    let dir = state.get_current_tab().dir.clone();
+    state.external_command = Some(ExternalCommand::new(shell()).current_dir(dir));
+    EventLoopAction::ContinueLoop
+}
+/// Runs `program` on the selected file with the terminal handed over to it.
+fn run_on_selected(state: &mut State, program: &str) {
+    let path = match state.get_current_panel() {
+        Ok(Some(panel)) => panel.borrow().selected_item().map(|x| x.path),
+        _ => None,
+    };
+    if let Some(path) = path {
+        let dir = state.get_current_tab().dir.clone();
+        state.external_command = Some(ExternalCommand::new(program).arg(&path).current_dir(dir));
+    }
+}
+fn edit_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    run_on_selected(state, &editor());
+    EventLoopAction::ContinueLoop
+}
+fn pager_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    run_on_selected(state, &pager());
+    EventLoopAction::ContinueLoop
+}
+fn run_opener(state: &mut State, opener: &Opener, paths: &[PathBuf]) {
+    let dir = state.get_current_tab().dir.clone();
+    let argv = match opener.command_line(paths, dir.as_deref()) {
+        Ok(argv) => argv,
+        Err(e) => {
+            state.message = format!("Opener '{}': {}", opener.name, e);
+            return;
+        }
+    };
+    if opener.detached {
+        if let Err(e) = spawn_detached(&argv[0], &argv[1..], dir.as_ref()) {
+            state.message = format!("Cannot run {}: {}", argv[0].to_string_lossy(), e);
+        }
+    } else {
+        let mut cmd = ExternalCommand::new(&argv[0]).current_dir(dir);
+        for arg in &argv[1..] {
+            cmd = cmd.arg(arg);
+        }
+        state.external_command = Some(cmd);
+    }
+}
+/// Opens the selected item: directories are entered, files are handed to the
+/// matching opener. With several matches the user picks one from a list;
+/// with `always_choose` the list also has the catch-all openers.
+fn open_selected(state: &mut State, always_choose: bool) -> EventLoopAction {
+    let selected = match state.get_current_panel() {
+        Ok(Some(panel)) => panel.borrow().selected_item(),
+        _ => None,
+    };
+    let selected = match selected {
+        Some(item) => item,
+        None => return EventLoopAction::ContinueLoop,
+    };
+    if selected.is_dir() {
+        let tab = state.get_current_tab_mut();
+        tab.cd_selected();
+        tab.update_preview();
+        return EventLoopAction::ContinueLoop;
+    }
+    let selected = selected.path;
+    // The selected file goes first, so `%f` always refers to it
+    let mut paths = vec![selected.clone()];
+    paths.extend(state.target_paths().into_iter().filter(|p| *p != selected));
+
+    let mime = sniff_mime(&selected);
+    let matching = openers_for(&state.openers, &selected, &mime, always_choose);
+    match matching.len() {
+        0 => state.message = format!("No opener for {} ({})", selected.display(), mime),
+        1 if !always_choose => run_opener(state, &matching[0], &paths),
+        _ => {
+            let items = matching
+                .iter()
+                .map(|o| format!("{}: {}", o.name, o.command))
+                .collect();
+            state.dialog = Some(Dialog::choose(
+                "Open with",
+                items,
+                Rc::new(move |state: &mut State, i: usize| {
+                    run_opener(state, &matching[i], &paths);
+                    EventLoopAction::ContinueLoop
+                }),
+            ));
+        }
+    }
+    EventLoopAction::ContinueLoop
+}
+fn open_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    open_selected(state, false)
+}
+fn open_with_action(state: &mut State, _args: &ActionArgs) -> EventLoopAction {
+    open_selected(state, true)
+}
+
+/// The type of an action parameter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamKind {
+    Str,
+    /// A path, which may start with `~`.
+    Path,
+    Bool,
+    /// One of a fixed set of words.
+    Choice(&'static [&'static str]),
+    /// Any number of strings; on the command line it takes all remaining words.
+    List,
+}
+
+impl ParamKind {
+    fn describe(&self) -> String {
+        match self {
+            ParamKind::Str => String::from("a string"),
+            ParamKind::Path => String::from("a path"),
+            ParamKind::Bool => String::from("true or false"),
+            ParamKind::Choice(choices) => format!("one of {}", choices.join(", ")),
+            ParamKind::List => String::from("a list of strings"),
+        }
+    }
+}
+
+/// A named parameter an action accepts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ParamKind,
+}
+
+const fn param(name: &'static str, kind: ParamKind) -> Param {
+    Param { name, kind }
+}
+
+/// The value of an action argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgValue {
+    Str(String),
+    /// A TOML integer; no parameter takes one, so it is only ever reported
+    /// as the wrong type.
+    Int(i64),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+impl ArgValue {
+    fn type_name(&self) -> &'static str {
+        match self {
+            ArgValue::Str(_) => "a string",
+            ArgValue::Int(_) => "an integer",
+            ArgValue::Bool(_) => "a boolean",
+            ArgValue::List(_) => "a list",
+        }
+    }
+}
+
+impl fmt::Display for ArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgValue::Str(s) => write!(f, "{}", s),
+            ArgValue::Int(i) => write!(f, "{}", i),
+            ArgValue::Bool(b) => write!(f, "{}", b),
+            ArgValue::List(l) => write!(f, "{}", l.join(" ")),
+        }
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s {
+        "true" | "on" | "yes" | "1" => Some(true),
+        "false" | "off" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Converts `value` to the kind `param` expects, so command-line words and
+/// TOML values end up the same.
+fn coerce(param: &Param, value: ArgValue) -> Result<ArgValue, String> {
+    let kind = param.kind;
+    let mismatch = |v: &ArgValue| {
+        format!("'{}' must be {}, not {}", param.name, kind.describe(), v.type_name())
+    };
+    match (kind, value) {
+        (ParamKind::Str, v @ ArgValue::Str(_)) | (ParamKind::Path, v @ ArgValue::Str(_)) => Ok(v),
+        (ParamKind::Choice(choices), ArgValue::Str(s)) => {
+            if choices.contains(&s.as_str()) {
+                Ok(ArgValue::Str(s))
+            } else {
+                Err(format!("'{}' must be {}, not '{}'", param.name, kind.describe(), s))
+            }
+        }
+        (ParamKind::Bool, v @ ArgValue::Bool(_)) => Ok(v),
+        // A boolean may also be given on the command line by its name, as in
+        // `sort size reverse`
+        (ParamKind::Bool, ArgValue::Str(s)) => parse_bool(&s)
+            .or_else(|| if s == param.name { Some(true) } else { None })
+            .map(ArgValue::Bool)
+            .ok_or_else(|| format!("'{}' must be {}, not '{}'", param.name, kind.describe(), s)),
+        (ParamKind::List, ArgValue::Str(s)) => Ok(ArgValue::List(vec![s])),
+        (ParamKind::List, v @ ArgValue::List(_)) => Ok(v),
+        (_, v) => Err(mismatch(&v)),
+    }
+}
+
+/// Arguments an action was invoked with, checked against its parameters.
+/// Key bindings without arguments pass none.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ActionArgs {
+    pub values: HashMap<String, ArgValue>,
+    /// The count typed before the keys, as in `5j`.
+    pub count: Option<usize>,
+}
+
+impl ActionArgs {
+    /// How many times to repeat the action: the count, or 1 without one.
+    pub fn repeat(&self) -> usize {
+        self.count.unwrap_or(1).max(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn str(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(ArgValue::Str(s)) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// A boolean argument, `false` if it was not given.
+    pub fn bool(&self, name: &str) -> bool {
+        matches!(self.values.get(name), Some(ArgValue::Bool(true)))
+    }
+
+    /// A list argument, empty if it was not given.
+    pub fn list(&self, name: &str) -> &[String] {
+        match self.values.get(name) {
+            Some(ArgValue::List(l)) => l.as_slice(),
+            _ => &[],
+        }
+    }
+}
+
+pub type Action = Rc<dyn Fn(&mut State, &ActionArgs) -> EventLoopAction>;
+
+/// The group an action is listed under in the help screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    Navigation,
+    Selection,
+    Files,
+    Programs,
+    Tabs,
+    Jobs,
+    Settings,
+}
+
+impl Category {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Navigation => "Navigation",
+            Category::Selection => "Selection",
+            Category::Files => "Files",
+            Category::Programs => "Programs",
+            Category::Tabs => "Tabs",
+            Category::Jobs => "Jobs",
+            Category::Settings => "Settings",
+        }
+    }
+}
+
+/// What an action does, for the help screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActionInfo {
+    pub category: Category,
+    /// One line, in the imperative.
+    pub description: &'static str,
+    /// Whether the action creates, changes or removes files.
+    pub modifies_fs: bool,
+}
+
+const fn info(category: Category, description: &'static str) -> ActionInfo {
+    ActionInfo {
+        category,
+        description,
+        modifies_fs: false,
+    }
+}
+
+/// Like `info`, for actions that change the filesystem.
+const fn fs_info(category: Category, description: &'static str) -> ActionInfo {
+    ActionInfo {
+        category,
+        description,
+        modifies_fs: true,
+    }
+}
+
+/// An entry of the action registry: the action, the parameters it takes and
+/// what it does.
+#[derive(Clone)]
+pub struct ActionDef {
+    pub action: Action,
+    pub params: &'static [Param],
+    pub info: ActionInfo,
+}
+
+impl ActionDef {
+    fn find_param(&self, name: &str) -> Result<&Param, String> {
+        self.params
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("Unknown argument '{}'", name))
+    }
+
+    /// Checks named arguments, e.g. from a TOML table.
+    pub fn args_from_named(&self, values: Vec<(String, ArgValue)>) -> Result<ActionArgs, String> {
+        let mut args = ActionArgs::default();
+        for (name, value) in values {
+            let param = self.find_param(&name)?;
+            args.values.insert(name, coerce(param, value)?);
+        }
+        Ok(args)
+    }
+
+    /// Assigns command-line words to the parameters in order. A list
+    /// parameter takes all remaining words.
+    pub fn args_from_words(&self, words: &[String]) -> Result<ActionArgs, String> {
+        let mut args = ActionArgs::default();
+        let mut words = words.iter();
+        for param in self.params.iter() {
+            let value = if param.kind == ParamKind::List {
+                let rest: Vec<String> = words.by_ref().cloned().collect();
+                if rest.is_empty() {
+                    continue;
+                }
+                ArgValue::List(rest)
+            } else {
+                match words.next() {
+                    Some(word) => ArgValue::Str(word.clone()),
+                    None => break,
+                }
+            };
+            args.values.insert(String::from(param.name), coerce(param, value)?);
+        }
+        if let Some(word) = words.next() {
+            return Err(format!("Unexpected argument '{}'", word));
+        }
+        Ok(args)
+    }
+}
+
+/// A bound action together with the arguments it was bound with.
+#[derive(Clone)]
+pub struct Binding {
+    pub name: String,
+    pub args: ActionArgs,
+    pub action: Action,
+}
+
+impl Binding {
+    pub fn new(name: &str, def: &ActionDef, args: ActionArgs) -> Self {
+        Self {
+            name: String::from(name),
+            args,
+            action: Rc::clone(&def.action),
+        }
+    }
+
+    /// Runs the action with the count typed before its keys, if any.
+    pub fn run(&self, state: &mut State, count: Option<usize>) -> EventLoopAction {
+        if count.is_none() {
+            return (self.action)(state, &self.args);
+        }
+        let args = ActionArgs {
+            count,
+            ..self.args.clone()
+        };
+        (self.action)(state, &args)
+    }
+}
+
+/// Writes the binding as a command line, e.g. `sort by=size reverse=true`.
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        let mut args: Vec<(&String, &ArgValue)> = self.args.values.iter().collect();
+        args.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, value) in args {
+            write!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+pub type ActionMap = HashMap<String, ActionDef>;
+
+fn register<F>(
+    actions: &mut ActionMap,
+    name: &str,
+    action: F,
+    params: &'static [Param],
+    info: ActionInfo,
+) where
+    F: Fn(&mut State, &ActionArgs) -> EventLoopAction + 'static,
+{
+    let def = ActionDef {
+        action: Rc::new(action),
+        params,
+        info,
+    };
+    actions.insert(String::from(name), def);
+}
 
-pub fn get_actions() -> HashMap<String, Action> {
-    let mut actions: HashMap<String, Action> = HashMap::new();
-    actions.insert(String::from("quit"), Rc::new(quit_action));
-    actions.insert(String::from("next_tab"), Rc::new(next_tab_action));
-    actions.insert(String::from("new_tab"), Rc::new(new_tab_action));
-    actions.insert(String::from("move_down"), Rc::new(move_down_action));
-    actions.insert(String::from("move_up"), Rc::new(move_up_action));
-    actions.insert(String::from("mark"), Rc::new(mark_action));
-    actions.insert(String::from("cd_parent"), Rc::new(cd_parent_action));
-    actions.insert(String::from("cd_selected"), Rc::new(cd_selected_action));
+pub fn get_actions() -> ActionMap {
+    use Category::*;
+    let mut actions = ActionMap::new();
+    let a = &mut actions;
+    register(a, "quit", quit_action, &[], info(Programs, "Quit zeus"));
+    register(a, "next_tab", next_tab_action, &[], info(Tabs, "Switch to the next tab"));
+    register(a, "new_tab", new_tab_action, &[], info(Tabs, "Open a new tab"));
+    register(a, "move_down", move_down_action, &[], info(Navigation, "Select the next entry"));
+    register(a, "move_up", move_up_action, &[], info(Navigation, "Select the previous entry"));
+    register(a, "page_down", page_down_action, &[], info(Navigation, "Move down a page"));
+    register(a, "page_up", page_up_action, &[], info(Navigation, "Move up a page"));
+    register(
+        a,
+        "half_page_down",
+        half_page_down_action,
+        &[],
+        info(Navigation, "Move down half a page"),
+    );
+    register(a, "half_page_up", half_page_up_action, &[], info(Navigation, "Move up half a page"));
+    register(a, "go_top", go_top_action, &[], info(Navigation, "Go to the first entry"));
+    register(a, "go_bottom", go_bottom_action, &[], info(Navigation, "Go to the last entry"));
+    register(
+        a,
+        "go_middle",
+        go_middle_action,
+        &[],
+        info(Navigation, "Go to the middle of the visible entries"),
+    );
+    register(a, "mark", mark_action, &[], info(Selection, "Mark or unmark the selected entry"));
+    register(a, "cd_parent", cd_parent_action, &[], info(Navigation, "Go to the parent directory"));
+    register(
+        a,
+        "cd_selected",
+        cd_selected_action,
+        &[],
+        info(Navigation, "Enter the selected directory"),
+    );
+    register(a, "yank", yank_action, &[], info(Selection, "Copy the marked files to the register"));
+    register(a, "cut", cut_action, &[], info(Selection, "Cut the marked files to the register"));
+    register(a, "paste", paste_action, &[], fs_info(Files, "Paste the register here"));
+    register(a, "toggle_jobs", toggle_jobs_action, &[], info(Jobs, "Show or hide background jobs"));
+    register(a, "next_job", next_job_action, &[], info(Jobs, "Select the next background job"));
+    register(a, "pause_job", pause_job_action, &[], info(Jobs, "Pause or resume the selected job"));
+    register(a, "cancel_job", cancel_job_action, &[], info(Jobs, "Cancel the selected job"));
+    register(a, "trash", trash_action, &[], fs_info(Files, "Move the marked files to the trash"));
+    register(
+        a,
+        "restore",
+        restore_action,
+        &[],
+        fs_info(Files, "Restore the marked files from the trash"),
+    );
+    register(a, "show_trash", show_trash_action, &[], info(Navigation, "Go to the trash"));
+    register(a, "delete", delete_action, &[], fs_info(Files, "Delete the marked files for good"));
+    register(a, "mkdir", mkdir_action, &MKDIR_PARAMS, fs_info(Files, "Create directories"));
+    register(a, "goto", goto_action, &[], info(Navigation, "Go to a directory by typing its path"));
+    register(a, "rename", rename_action, &[], fs_info(Files, "Rename the selected file"));
+    register(
+        a,
+        "bulk_rename",
+        bulk_rename_action,
+        &[],
+        fs_info(Files, "Rename the marked files in the editor"),
+    );
+    register(
+        a,
+        "shell",
+        shell_action,
+        &[],
+        info(Programs, "Start a shell in the current directory"),
+    );
+    register(a, "edit", edit_action, &[], info(Programs, "Open the selected file in the editor"));
+    register(a, "pager", pager_action, &[], info(Programs, "Open the selected file in the pager"));
+    register(a, "open", open_action, &[], info(Programs, "Open the selected file or directory"));
+    register(
+        a,
+        "open_with",
+        open_with_action,
+        &[],
+        info(Programs, "Choose a program to open the selected file"),
+    );
+    register(a, "cd", cd_action, &CD_PARAMS, info(Navigation, "Go to a directory"));
+    register(
+        a,
+        "command_line",
+        command_line_action,
+        &COMMAND_LINE_PARAMS,
+        info(Settings, "Open the command line"),
+    );
+    register(a, "set", set_action, &SET_PARAMS, info(Settings, "Change or show options"));
+    register(a, "search", search_action, &[], info(Navigation, "Search for a name as you type"));
+    register(a, "search_next", search_next_action, &[], info(Navigation, "Go to the next match"));
+    register(
+        a,
+        "search_prev",
+        search_prev_action,
+        &[],
+        info(Navigation, "Go to the previous match"),
+    );
+    register(
+        a,
+        "filter",
+        filter_action,
+        &FILTER_PARAMS,
+        info(Navigation, "Show only the entries matching a pattern"),
+    );
+    register(
+        a,
+        "clear_filter",
+        clear_filter_action,
+        &[],
+        info(Navigation, "Show all entries again"),
+    );
+    register(
+        a,
+        "find",
+        find_action,
+        &[],
+        info(Navigation, "Fuzzy find a path below the current directory"),
+    );
+    register(a, "help", help_action, &[], info(Settings, "List all actions and their keys"));
+    register(a, "sort", sort_action, &SORT_PARAMS, info(Settings, "Change the sort order"));
     actions
 }
@@ -0,0 +1,210 @@
+// The `:` command language.
+//
+// A command line is split into words like a shell would split it. The first
+// word names an action from `get_actions` and the rest become its
+// `ActionArgs`, so every action can be run without a key binding. A few
+// builtins that change the configuration rather than the state are handled
+// here directly.
+
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::zeuslib::actions::{ActionDef, Binding, ParamKind};
+use crate::zeuslib::config::{Config, KeySource};
+use crate::zeuslib::events::loopaction::EventLoopAction;
+use crate::zeuslib::input::KeySequence;
+use crate::zeuslib::state::options::OPTION_NAMES;
+use crate::zeuslib::state::State;
+use crate::zeuslib::ui::dialog::Dialog;
+use crate::zeuslib::utils::fs::expand_tilde;
+use crate::zeuslib::utils::shellwords::split;
+
+/// Commands that are not actions because they need the configuration.
+const BUILTINS: &[&str] = &["map", "maps", "unmap"];
+
+/// Parses and runs one command line, reporting problems in the message line.
+pub fn run_command(state: &mut State, config: &mut Config, line: &str) -> EventLoopAction {
+    let words = match split(line) {
+        Ok(words) => words,
+        Err(e) => {
+            state.message = e;
+            return EventLoopAction::ContinueLoop;
+        }
+    };
+    let (name, words) = match words.split_first() {
+        Some((name, words)) => (name.as_str(), words),
+        None => return EventLoopAction::ContinueLoop,
+    };
+    match name {
+        "map" => {
+            state.message = map_command(config, words).unwrap_or_else(|e| e);
+            return EventLoopAction::ContinueLoop;
+        }
+        "unmap" => {
+            state.message = unmap_command(config, words).unwrap_or_else(|e| e);
+            return EventLoopAction::ContinueLoop;
+        }
+        "maps" => {
+            state.dialog = Some(Dialog::message("Mappings", config.describe_mappings()));
+            return EventLoopAction::ContinueLoop;
+        }
+        _ => {}
+    }
+    let def = match config.actions.get(name) {
+        Some(def) => def,
+        None => {
+            state.message = format!("Unknown command: {}", name);
+            return EventLoopAction::ContinueLoop;
+        }
+    };
+    match def.args_from_words(words) {
+        Ok(args) => {
+            let action = Rc::clone(&def.action);
+            action(state, &args)
+        }
+        Err(e) => {
+            state.message = format!("{}: {}", name, e);
+            EventLoopAction::ContinueLoop
+        }
+    }
+}
+
+/// `map <keys> <command> [args...]` binds a key sequence to a command line.
+fn map_command(config: &mut Config, words: &[String]) -> Result<String, String> {
+    let usage = || String::from("Usage: map <keys> <command> [args...]");
+    let (keys, rest) = words.split_first().ok_or_else(usage)?;
+    let (name, words) = rest.split_first().ok_or_else(usage)?;
+    let seq = KeySequence::parse(keys).ok_or_else(|| format!("Invalid key sequence: {}", keys))?;
+    let def = config
+        .actions
+        .get(name)
+        .ok_or_else(|| format!("Unknown command: {}", name))?;
+    let args = def
+        .args_from_words(words)
+        .map_err(|e| format!("{}: {}", name, e))?;
+    let binding = Binding::new(name, def, args);
+    config.map_key(seq, binding, KeySource::Command);
+    Ok(format!("Mapped {} to {}", keys, rest.join(" ")))
+}
+
+/// `unmap <keys>` removes the binding of a key sequence.
+fn unmap_command(config: &mut Config, words: &[String]) -> Result<String, String> {
+    let keys = match words {
+        [keys] => keys,
+        _ => return Err(String::from("Usage: unmap <keys>")),
+    };
+    let seq = KeySequence::parse(keys).ok_or_else(|| format!("Invalid key sequence: {}", keys))?;
+    if config.unmap_key(&seq) {
+        Ok(format!("Unmapped {}", keys))
+    } else {
+        Err(format!("No mapping for {}", keys))
+    }
+}
+
+fn command_names(config: &Config) -> Vec<String> {
+    let mut names: Vec<String> = config.actions.keys().cloned().collect();
+    names.extend(BUILTINS.iter().map(|x| String::from(*x)));
+    names.sort();
+    names
+}
+
+fn complete_path(dir: Option<&Path>, word: &str) -> Vec<String> {
+    let (head, tail) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let base = expand_tilde(head);
+    let base: PathBuf = match dir {
+        Some(dir) if base.is_relative() => dir.join(base),
+        _ => base,
+    };
+    let mut names: Vec<String> = match base.read_dir() {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|e| {
+                let name = e.file_name().into_string().ok()?;
+                if !name.starts_with(tail) || (name.starts_with('.') && !tail.starts_with('.')) {
+                    return None;
+                }
+                let is_dir = e.path().is_dir();
+                Some(if is_dir { format!("{}/", name) } else { name })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    names.sort();
+    names.into_iter().map(|n| format!("{}{}", head, n)).collect()
+}
+
+fn starting_with(list: &[&str], word: &str) -> Vec<String> {
+    list.iter()
+        .filter(|x| x.starts_with(word))
+        .map(|x| String::from(*x))
+        .collect()
+}
+
+/// Completes the `index`th command-line word of an action from the kind of
+/// parameter it is assigned to.
+fn complete_param(def: &ActionDef, index: usize, dir: Option<&Path>, word: &str) -> Vec<String> {
+    let param = def
+        .params
+        .get(index)
+        .or_else(|| def.params.last().filter(|p| p.kind == ParamKind::List));
+    let param = match param {
+        Some(param) => param,
+        None => return Vec::new(),
+    };
+    match param.kind {
+        ParamKind::Choice(choices) => starting_with(choices, word),
+        ParamKind::Bool => starting_with(&["true", "false", param.name], word),
+        ParamKind::Path | ParamKind::List => complete_path(dir, word),
+        ParamKind::Str => Vec::new(),
+    }
+}
+
+fn complete_word(config: &Config, dir: Option<&Path>, words: &[&str], word: &str) -> Vec<String> {
+    match words {
+        [] => command_names(config)
+            .into_iter()
+            .filter(|x| x.starts_with(word))
+            .collect(),
+        ["set", ..] => starting_with(OPTION_NAMES, word),
+        ["map"] | ["maps", ..] | ["unmap", ..] => Vec::new(),
+        ["map", _, rest @ ..] => complete_word(config, dir, rest, word),
+        [name, rest @ ..] => match config.actions.get(*name) {
+            Some(def) => complete_param(def, rest.len(), dir, word),
+            None => Vec::new(),
+        },
+    }
+}
+
+/// Completes the last word of `line`, returning the completed lines.
+///
+/// The first word completes to command names, arguments to the values their
+/// parameter accepts, with paths relative to `dir`.
+pub fn complete(config: &Config, dir: Option<&Path>, line: &str) -> Vec<String> {
+    let (head, word) = match line.rfind(' ') {
+        Some(i) => (&line[..=i], &line[i + 1..]),
+        None => ("", line),
+    };
+    let words: Vec<&str> = head.split_whitespace().collect();
+    complete_word(config, dir, &words, word)
+        .into_iter()
+        .map(|x| format!("{}{}", head, x))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes_commands_and_arguments() {
+        let config = Config::default();
+        let lines = complete(&config, None, "bulk");
+        assert_eq!(lines, vec!["bulk_rename"]);
+        assert_eq!(complete(&config, None, "sort mt"), vec!["sort mtime"]);
+        assert_eq!(complete(&config, None, "map gh cd_p"), vec!["map gh cd_parent"]);
+        assert!(complete(&config, None, "set h").contains(&String::from("set hidden")));
+    }
+}
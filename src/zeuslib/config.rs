@@ -1,10 +1,37 @@
 mod cfgfile;
+mod cfgerror;
 mod cfgdata;
+mod keytrie;
+mod opener;
 
 use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
 
 use crate::zeuslib::actions::*;
 use crate::zeuslib::input::KeySequence;
 
 pub use self::cfgdata::*;
-pub type KeyMap = HashMap<KeySequence, Action>;
+pub use self::cfgerror::*;
+pub use self::keytrie::*;
+pub use self::opener::*;
+pub type KeyMap = HashMap<KeySequence, Binding>;
+
+/// Where a key binding was made, for `:maps`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeySource {
+    Default,
+    File(PathBuf),
+    /// `:map` on the command line.
+    Command,
+}
+
+impl fmt::Display for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeySource::Default => write!(f, "default"),
+            KeySource::File(path) => write!(f, "{}", path.display()),
+            KeySource::Command => write!(f, ":map"),
+        }
+    }
+}
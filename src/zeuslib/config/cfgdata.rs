@@ -1,68 +1,361 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::rc::Rc;
+use std::path::Path;
 use termion::event::Key;
 use toml::Value;
 
 use crate::zeuslib::actions::*;
 use crate::zeuslib::input::KeySequence;
+use crate::zeuslib::state::Options;
 
-use crate::zeuslib::config::KeyMap;
+use crate::zeuslib::config::{default_openers, ConfigError, KeyMap, KeySource, KeyTrie, Opener};
 use crate::zeuslib::config::cfgfile::*;
 
 
 
 pub struct Config {
     pub key_map: KeyMap,
+    /// The same bindings as `key_map`, by key, for dispatching.
+    pub key_trie: KeyTrie,
+    /// Where each binding in `key_map` was made.
+    pub key_sources: HashMap<KeySequence, KeySource>,
+    /// Every action by name, for the command line.
+    pub actions: ActionMap,
+    pub openers: Vec<Opener>,
+    /// Initial values of the `:set` options.
+    pub options: Options,
+    /// Problems found while loading config files; the offending entries
+    /// are left out.
+    pub errors: Vec<ConfigError>,
 }
 
 impl Config {
-    pub fn from_file(path: &PathBuf) -> Option<Self> {
-        if !path.is_file() {
-            return None;
+    /// The defaults with the config file at `path` applied over them.
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        config.load_file(path)?;
+        Ok(config)
+    }
+
+    /// Applies the config file at `path` over the current config, so files
+    /// loaded later override bindings and openers from earlier ones.
+    ///
+    /// Fails only if the file cannot be read or is not valid TOML. Invalid
+    /// entries are skipped and recorded in `errors`.
+    pub fn load_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        self.load_file_with(path, true)
+    }
+
+    /// Like `load_file`, for a project-local config file. Whoever controls
+    /// the directory controls this file, so it may not define openers,
+    /// which run commands.
+    pub fn load_project_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        self.load_file_with(path, false)
+    }
+
+    fn load_file_with(&mut self, path: &Path, trusted: bool) -> Result<(), ConfigError> {
+        let source = fs::read_to_string(path).map_err(|e| ConfigError::io(path, &e))?;
+        let value = source
+            .parse::<Value>()
+            .map_err(|e| ConfigError::parse(path, &e))?;
+        let mut errors = self.process_config_file(&value, path, trusted);
+        for e in errors.iter_mut() {
+            e.locate(path, &source);
         }
-        let value: Option<String> = {
-            let contents = fs::read_to_string(path);
-            if let Ok(contents) = contents {
-                Some(contents)
-            } else {
-                None
-            }
-        };
-        if let Some(value) = value {
-            if let Ok(value) = value.parse::<Value>() {
-                return Some(Self::process_config_file(&value));
+        self.errors.append(&mut errors);
+        Ok(())
+    }
+
+    fn process_config_file(
+        &mut self,
+        value: &Value,
+        path: &Path,
+        trusted: bool,
+    ) -> Vec<ConfigError> {
+        let mut errors: Vec<ConfigError> = Vec::new();
+        if !trusted && value.get("openers").is_some() {
+            let reason = "Not allowed in a project config file";
+            errors.push(ConfigError::at_key("openers", reason));
+        }
+        for k in process_config_unmap(value, &mut errors) {
+            self.unmap_key(&k);
+        }
+        let mut key_map = process_config_mappings(value, &self.actions, &mut errors);
+        key_map.extend(process_config_keys(value, &self.actions, &mut errors));
+        for (k, binding) in key_map {
+            self.map_key(k, binding, KeySource::File(path.to_path_buf()));
+        }
+        process_config_options(value, &mut self.options, &mut errors);
+        if trusted {
+            if let Some(openers) = process_config_openers(value, &mut errors) {
+                self.merge_openers(openers);
             }
         }
+        errors
+    }
 
-        None
+    /// Puts `openers` in front of the current ones, replacing those with the
+    /// same name.
+    fn merge_openers(&mut self, mut openers: Vec<Opener>) {
+        self.openers
+            .retain(|x| !openers.iter().any(|o| o.name == x.name));
+        openers.append(&mut self.openers);
+        self.openers = openers;
     }
 
-    fn process_config_file(value: &Value) -> Self {
-        Self {
-            key_map: process_config_mappings(&value),
-        }
+    pub fn map_key(&mut self, k: KeySequence, binding: Binding, source: KeySource) {
+        self.key_sources.insert(k.clone(), source);
+        self.key_trie.insert(&k, binding.clone());
+        self.key_map.insert(k, binding);
+    }
+
+    /// Removes the binding for `k`. Returns whether there was one.
+    pub fn unmap_key(&mut self, k: &KeySequence) -> bool {
+        self.key_sources.remove(k);
+        self.key_trie.remove(k);
+        self.key_map.remove(k).is_some()
+    }
+
+    /// The bindings that continue `prefix`, sorted by the keys left to type.
+    pub fn bindings_with_prefix(&self, prefix: &KeySequence) -> Vec<(KeySequence, &Binding)> {
+        let mut found = self.key_trie.continuations(prefix);
+        found.sort_by_cached_key(|(k, _)| k.to_string());
+        found
+    }
+
+    /// One line per binding, sorted by keys: the keys, the command it runs
+    /// and where it was made.
+    pub fn describe_mappings(&self) -> Vec<String> {
+        let mut maps: Vec<(String, String, String)> = self
+            .key_map
+            .iter()
+            .map(|(k, binding)| {
+                let source = self
+                    .key_sources
+                    .get(k)
+                    .map(|x| x.to_string())
+                    .unwrap_or_default();
+                (k.to_string(), binding.to_string(), source)
+            })
+            .collect();
+        maps.sort();
+        let width = maps.iter().map(|x| x.0.chars().count()).max().unwrap_or(0);
+        let cmd_width = maps.iter().map(|x| x.1.chars().count()).max().unwrap_or(0);
+        maps.into_iter()
+            .map(|(keys, cmd, source)| {
+                format!("{:w$}  {:cw$}  {}", keys, cmd, source, w = width, cw = cmd_width)
+            })
+            .collect()
     }
+}
 
-    pub fn default() -> Self {
+impl Default for Config {
+    fn default() -> Self {
+        let actions = get_actions();
+        let bind = |name: &str| Binding::new(name, &actions[name], ActionArgs::default());
         let mut config = Self {
             key_map: HashMap::new(),
+            key_trie: KeyTrie::default(),
+            key_sources: HashMap::new(),
+            actions: actions.clone(),
+            openers: default_openers(),
+            options: Options::default(),
+            errors: Vec::new(),
         };
-        let actions = get_actions();
-        config.map_key(KeySequence::from_keys(&[Key::Char('q')]), &actions["quit"]);
+        config.map_key(KeySequence::from_keys(&[Key::Char('q')]), bind("quit"), KeySource::Default);
         config.map_key(
             KeySequence::from_keys(&[Key::Char('j')]),
-            &actions["move_down"],
+            bind("move_down"),
+            KeySource::Default,
         );
         config.map_key(
             KeySequence::from_keys(&[Key::Char('k')]),
-            &actions["move_up"],
+            bind("move_up"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Ctrl('f')]),
+            bind("page_down"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::PageDown]),
+            bind("page_down"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Ctrl('b')]),
+            bind("page_up"),
+            KeySource::Default,
+        );
+        config.map_key(KeySequence::from_keys(&[Key::PageUp]), bind("page_up"), KeySource::Default);
+        config.map_key(
+            KeySequence::from_keys(&[Key::Ctrl('d')]),
+            bind("half_page_down"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Ctrl('u')]),
+            bind("half_page_up"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('g'), Key::Char('g')]),
+            bind("go_top"),
+            KeySource::Default,
+        );
+        config.map_key(KeySequence::from_keys(&[Key::Home]), bind("go_top"), KeySource::Default);
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('G')]),
+            bind("go_bottom"),
+            KeySource::Default,
+        );
+        config.map_key(KeySequence::from_keys(&[Key::End]), bind("go_bottom"), KeySource::Default);
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('M')]),
+            bind("go_middle"),
+            KeySource::Default,
+        );
+        config.map_key(KeySequence::from_keys(&[Key::Char(' ')]), bind("mark"), KeySource::Default);
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('/')]),
+            bind("search"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('n')]),
+            bind("search_next"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('N')]),
+            bind("search_prev"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Ctrl('p')]),
+            bind("find"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('f')]),
+            bind("filter"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('F')]),
+            bind("clear_filter"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('y'), Key::Char('y')]),
+            bind("yank"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('x'), Key::Char('x')]),
+            bind("cut"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('p'), Key::Char('p')]),
+            bind("paste"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('d'), Key::Char('d')]),
+            bind("trash"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('d'), Key::Char('D')]),
+            bind("delete"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('c'), Key::Char('w')]),
+            bind("rename"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('h')]),
+            bind("cd_parent"),
+            KeySource::Default,
+        );
+        config.map_key(KeySequence::from_keys(&[Key::Char('l')]), bind("open"), KeySource::Default);
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('\n')]),
+            bind("open"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('o')]),
+            bind("open_with"),
+            KeySource::Default,
+        );
+        config.map_key(KeySequence::from_keys(&[Key::Down]), bind("move_down"), KeySource::Default);
+        config.map_key(KeySequence::from_keys(&[Key::Up]), bind("move_up"), KeySource::Default);
+        config.map_key(KeySequence::from_keys(&[Key::Left]), bind("cd_parent"), KeySource::Default);
+        config.map_key(KeySequence::from_keys(&[Key::Right]), bind("open"), KeySource::Default);
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char(':')]),
+            bind("command_line"),
+            KeySource::Default,
+        );
+        config.map_key(
+            KeySequence::from_keys(&[Key::Char('?')]),
+            bind("help"),
+            KeySource::Default,
         );
-        config.map_key(KeySequence::from_keys(&[Key::Char(' ')]), &actions["mark"]);
         config
     }
-    pub fn map_key(&mut self, k: KeySequence, action: &Action) {
-        self.key_map.insert(k, Rc::clone(action));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_layer_over_defaults() {
+        let value: Value = r#"
+            unmap = ["dD"]
+            [keys]
+            gh = "cd_parent"
+            q = "new_tab"
+        "#
+        .parse()
+        .unwrap();
+        let mut config = Config::default();
+        let path = Path::new("zeus.toml");
+        let errors = config.process_config_file(&value, path, true);
+        assert!(errors.is_empty());
+
+        let seq = |s| KeySequence::parse(s).unwrap();
+        assert_eq!(config.key_map[&seq("j")].name, "move_down");
+        assert_eq!(config.key_sources[&seq("j")], KeySource::Default);
+        assert_eq!(config.key_map[&seq("q")].name, "new_tab");
+        assert_eq!(config.key_sources[&seq("gh")], KeySource::File(path.to_path_buf()));
+        assert!(!config.key_map.contains_key(&seq("dD")));
+        assert!(config.key_map.contains_key(&seq("dd")));
+    }
+
+    #[test]
+    fn project_files_cannot_define_openers() {
+        let value: Value = r#"
+            [keys]
+            gh = "cd_parent"
+            [openers.evil]
+            command = "rm -rf ~"
+            mime = ["text/"]
+        "#
+        .parse()
+        .unwrap();
+        let mut config = Config::default();
+        let errors = config.process_config_file(&value, Path::new(".zeus.toml"), false);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].key.as_deref(), Some("openers"));
+        assert!(config.openers.iter().all(|o| o.name != "evil"));
+        let seq = KeySequence::parse("gh").unwrap();
+        assert_eq!(config.key_map[&seq].name, "cd_parent");
     }
 }
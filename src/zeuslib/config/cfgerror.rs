@@ -0,0 +1,217 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A problem with the config file.
+///
+/// Errors found while reading the parsed TOML only know the key they are
+/// about; `locate` later finds its line and column in the file's source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub file: Option<PathBuf>,
+    /// 1-based line and column.
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// Dotted path of the offending key, e.g. `keys.gw`.
+    pub key: Option<String>,
+    pub reason: String,
+}
+
+impl ConfigError {
+    pub fn new(reason: &str) -> Self {
+        Self {
+            file: None,
+            line: None,
+            column: None,
+            key: None,
+            reason: String::from(reason),
+        }
+    }
+
+    /// An error about the value at the dotted `key`.
+    pub fn at_key(key: &str, reason: &str) -> Self {
+        Self {
+            key: Some(String::from(key)),
+            ..Self::new(reason)
+        }
+    }
+
+    pub fn io(file: &Path, e: &std::io::Error) -> Self {
+        Self {
+            file: Some(file.to_path_buf()),
+            ..Self::new(&e.to_string())
+        }
+    }
+
+    /// A TOML syntax error, which comes with its own position.
+    pub fn parse(file: &Path, e: &toml::de::Error) -> Self {
+        let mut reason = e.to_string();
+        let position = e.line_col();
+        if position.is_some() {
+            // The message ends in its own " at line X column Y"
+            if let Some(i) = reason.rfind(" at line ") {
+                reason.truncate(i);
+            }
+        }
+        Self {
+            file: Some(file.to_path_buf()),
+            line: position.map(|(line, _)| line + 1),
+            column: position.map(|(_, col)| col + 1),
+            ..Self::new(&reason)
+        }
+    }
+
+    /// Fills in the file and, from `source`, the position of the key.
+    pub fn locate(&mut self, file: &Path, source: &str) {
+        self.file = Some(file.to_path_buf());
+        if self.line.is_some() {
+            return;
+        }
+        if let Some(key) = &self.key {
+            let path = split_key_path(key);
+            let path: Vec<&str> = path.iter().map(|x| x.as_str()).collect();
+            if let Some((line, column)) = find_key(source, &path) {
+                self.line = Some(line + 1);
+                self.column = Some(column + 1);
+            }
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+                if let Some(column) = self.column {
+                    write!(f, "{}:", column)?;
+                }
+            }
+            write!(f, " ")?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, "{}: ", key)?;
+        }
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Joins keys into a dotted path, quoting those that are not bare TOML keys.
+pub fn key_path(keys: &[&str]) -> String {
+    let parts: Vec<String> = keys
+        .iter()
+        .map(|k| {
+            let bare = !k.is_empty()
+                && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if bare {
+                String::from(*k)
+            } else {
+                format!("{:?}", k)
+            }
+        })
+        .collect();
+    parts.join(".")
+}
+
+fn unquote(s: &str) -> String {
+    let s = s.trim();
+    let quoted = s.len() >= 2
+        && ((s.starts_with('"') && s.ends_with('"')) || (s.starts_with('\'') && s.ends_with('\'')));
+    if quoted {
+        String::from(&s[1..s.len() - 1])
+    } else {
+        String::from(s)
+    }
+}
+
+/// Splits a dotted key path, keeping dots inside quotes.
+fn split_key_path(s: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut cur = String::new();
+    let mut quote: Option<char> = None;
+    for c in s.chars() {
+        match (c, quote) {
+            ('"', None) | ('\'', None) => {
+                quote = Some(c);
+                cur.push(c);
+            }
+            (c, Some(q)) if c == q => {
+                quote = None;
+                cur.push(c);
+            }
+            ('.', None) => parts.push(unquote(&std::mem::take(&mut cur))),
+            (c, _) => cur.push(c),
+        }
+    }
+    parts.push(unquote(&cur));
+    parts
+}
+
+/// The key assigned to on a `key = value` line.
+fn assigned_key(line: &str) -> Option<String> {
+    let line = line.trim();
+    let end = if line.starts_with('"') || line.starts_with('\'') {
+        let q = line.chars().next()?;
+        line[1..].find(q)? + 2
+    } else {
+        line.find('=')?
+    };
+    if !line[end..].trim_start().starts_with('=') {
+        return None;
+    }
+    Some(unquote(&line[..end]))
+}
+
+/// Finds the 0-based line and column where `path` is defined: either a
+/// `[table]` header or a `key = value` line inside its parent table. Falls
+/// back to the closest parent that can be found.
+fn find_key(source: &str, path: &[&str]) -> Option<(usize, usize)> {
+    if path.is_empty() {
+        return None;
+    }
+    let (key, parent) = path.split_last()?;
+    let mut table: Vec<String> = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let column = line.len() - trimmed.len();
+        if trimmed.starts_with('[') {
+            let header = trimmed.trim_start_matches('[');
+            let header = header.split(']').next().unwrap_or_default();
+            table = split_key_path(header.trim());
+            if table.iter().map(|x| x.as_str()).eq(path.iter().copied()) {
+                return Some((i, column));
+            }
+            continue;
+        }
+        if table.iter().map(|x| x.as_str()).eq(parent.iter().copied())
+            && assigned_key(trimmed).as_deref() == Some(*key)
+        {
+            return Some((i, column));
+        }
+    }
+    find_key(source, parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locates_keys() {
+        let source = "[mappings]\nquit = [\"q\"]\n\n[keys]\n  \"<C-a>\" = \"quit\"\n\n[openers.pdf]\ncommand = \"zathura\"\n";
+        let mut e = ConfigError::at_key("keys.\"<C-a>\"", "bad");
+        e.locate(Path::new("zeus.toml"), source);
+        assert_eq!((e.line, e.column), (Some(5), Some(3)));
+        assert_eq!(e.to_string(), "zeus.toml:5:3: keys.\"<C-a>\": bad");
+
+        let mut e = ConfigError::at_key("openers.pdf.glob", "bad");
+        e.locate(Path::new("zeus.toml"), source);
+        assert_eq!(e.line, Some(7));
+
+        let mut e = ConfigError::at_key("mappings.quit", "bad");
+        e.locate(Path::new("zeus.toml"), source);
+        assert_eq!(e.line, Some(2));
+    }
+}
@@ -1,68 +1,290 @@
 use crate::zeuslib::actions::*;
-use crate::zeuslib::config::KeyMap;
-use crate::zeuslib::input::KeySequence;
+use crate::zeuslib::config::{key_path, ConfigError, KeyMap, Opener};
+use crate::zeuslib::input::{parse_key, KeySequence};
+use crate::zeuslib::state::Options;
 
-use std::iter::FromIterator;
-
-use std::rc::Rc;
 use termion::event::Key;
 
-use regex::Regex;
+use crate::zeuslib::utils::glob::Glob;
+
 use toml::Value;
 
-lazy_static! {
-    static ref MODIFIED_KEY_RE: Regex = Regex::new("(C|c|M|m)-(.)").unwrap();
-    static ref SIMPLE_KEY_RE: Regex = Regex::new("(.)").unwrap();
-}
+/// Reads the `[mappings]` table, which maps action names to an array of keys.
+pub fn process_config_mappings(
+    toml_value: &Value,
+    actions: &ActionMap,
+    errors: &mut Vec<ConfigError>,
+) -> KeyMap {
+    let mut key_map: KeyMap = KeyMap::new();
+    let mappings = match toml_value.get("mappings") {
+        Some(Value::Table(mappings)) => mappings,
+        Some(_) => {
+            errors.push(ConfigError::at_key("mappings", "Expected a table"));
+            return key_map;
+        }
+        None => return key_map,
+    };
+    for (action, keys) in mappings {
+        let key = key_path(&["mappings", action]);
+        let def = match actions.get(action) {
+            Some(def) => def,
+            None => {
+                errors.push(ConfigError::at_key(&key, &format!("Unknown action '{}'", action)));
+                continue;
+            }
+        };
 
-fn parse_key_string(key_str: &str) -> Option<Key> {
-    if let Some(caps) = (*MODIFIED_KEY_RE).captures(key_str) {
-        let modifier = caps[1].to_string().to_uppercase();
-        let c = &caps[2].chars().nth(0).unwrap();
+        let keyseq = match keys.as_array() {
+            Some(keyseq) => keyseq,
+            None => {
+                errors.push(ConfigError::at_key(&key, "Expected an array of keys"));
+                continue;
+            }
+        };
+        let keyseq: Result<Vec<Key>, String> = keyseq
+            .iter()
+            .map(|x| {
+                let s = x.as_str().unwrap_or_default();
+                parse_key(s).ok_or_else(|| format!("Invalid key '{}'", s))
+            })
+            .collect();
+        match keyseq {
+            Ok(keyseq) if !keyseq.is_empty() => {
+                let binding = Binding::new(action, def, ActionArgs::default());
+                key_map.insert(KeySequence::from_keys(&keyseq), binding);
+            }
+            Ok(_) => errors.push(ConfigError::at_key(&key, "No keys given")),
+            Err(e) => errors.push(ConfigError::at_key(&key, &e)),
+        }
+    }
+    key_map
+}
 
-        if modifier == "C" {
-            Some(Key::Ctrl(*c))
-        } else if modifier == "M" {
-            Some(Key::Alt(*c))
-        } else {
-            None
+/// Reads `unmap`, an array of key sequences whose earlier bindings, such as
+/// the defaults, are removed before this file's own are added.
+pub fn process_config_unmap(toml_value: &Value, errors: &mut Vec<ConfigError>) -> Vec<KeySequence> {
+    let keys = match toml_value.get("unmap") {
+        Some(Value::Array(keys)) => keys,
+        Some(_) => {
+            errors.push(ConfigError::at_key("unmap", "Expected an array of key sequences"));
+            return Vec::new();
+        }
+        None => return Vec::new(),
+    };
+    let mut seqs: Vec<KeySequence> = Vec::new();
+    for k in keys {
+        match k.as_str().and_then(KeySequence::parse) {
+            Some(seq) => seqs.push(seq),
+            None => errors.push(ConfigError::at_key(
+                "unmap",
+                &format!("Invalid key sequence {}", k),
+            )),
         }
-    } else if (*SIMPLE_KEY_RE).is_match(key_str) {
-        let c = key_str.chars().nth(0).unwrap();
-        Some(Key::Char(c))
-    } else {
-        None
     }
+    seqs
 }
 
-pub fn process_config_mappings(toml_value: &Value) -> KeyMap {
-    let fallback = toml::value::Table::new();
-    let mappings = toml_value["mappings"].as_table().unwrap_or(&fallback);
-    let mut key_map: KeyMap = KeyMap::new();
-    let actions = get_actions();
-    for (action, keys) in mappings {
-        if !actions.contains_key(action) {
+fn toml_to_arg(value: &Value) -> Option<ArgValue> {
+    match value {
+        Value::String(s) => Some(ArgValue::Str(s.clone())),
+        Value::Integer(i) => Some(ArgValue::Int(*i)),
+        Value::Boolean(b) => Some(ArgValue::Bool(*b)),
+        Value::Array(a) => a
+            .iter()
+            .map(|x| x.as_str().map(String::from))
+            .collect::<Option<Vec<String>>>()
+            .map(ArgValue::List),
+        _ => None,
+    }
+}
+
+/// Reads one entry of the `[keys]` table: either an action name or a table
+/// with the action name under `action` and its arguments next to it.
+fn binding_from_toml(value: &Value, actions: &ActionMap) -> Result<Binding, String> {
+    let (name, table) = match value {
+        Value::String(name) => (name.as_str(), None),
+        Value::Table(table) => match table.get("action").and_then(|x| x.as_str()) {
+            Some(name) => (name, Some(table)),
+            None => return Err(String::from("Missing 'action'")),
+        },
+        _ => return Err(String::from("Expected an action name or a table")),
+    };
+    let def = actions
+        .get(name)
+        .ok_or_else(|| format!("Unknown action '{}'", name))?;
+    let mut values: Vec<(String, ArgValue)> = Vec::new();
+    for (k, v) in table.into_iter().flatten() {
+        if k == "action" {
             continue;
         }
+        let v = toml_to_arg(v).ok_or_else(|| format!("Unsupported value for '{}'", k))?;
+        values.push((k.clone(), v));
+    }
+    let args = def
+        .args_from_named(values)
+        .map_err(|e| format!("{}: {}", name, e))?;
+    Ok(Binding::new(name, def, args))
+}
 
-        let action = Rc::clone(&actions[action]);
-
-        let keyseq = keys.as_array();
-        if let Some(keyseq) = keyseq {
-            let keyseq = {
-                let key_chars = keyseq
-                    .iter()
-                    .map(|x| {
-                        let s = x.as_str();
-                        let s = if let Some(s) = s { s } else { "" };
-                        parse_key_string(&s)
-                    })
-                    .filter(|x| x.is_some())
-                    .map(|x| x.unwrap());
-                Vec::from_iter(key_chars)
-            };
-            key_map.insert(KeySequence::from_keys(&keyseq), action);
+/// Reads the `[keys]` table, which maps key sequences to actions with
+/// arguments:
+///
+/// ```toml
+/// [keys]
+/// gw = { action = "cd", path = "~/work" }
+/// ss = { action = "sort", by = "size", reverse = true }
+/// gh = "cd_parent"
+/// ```
+///
+/// Invalid entries are skipped and reported in `errors`.
+pub fn process_config_keys(
+    toml_value: &Value,
+    actions: &ActionMap,
+    errors: &mut Vec<ConfigError>,
+) -> KeyMap {
+    let mut key_map = KeyMap::new();
+    let table = match toml_value.get("keys") {
+        Some(Value::Table(table)) => table,
+        Some(_) => {
+            errors.push(ConfigError::at_key("keys", "Expected a table"));
+            return key_map;
+        }
+        None => return key_map,
+    };
+    for (keys, value) in table {
+        let key = key_path(&["keys", keys]);
+        let seq = match KeySequence::parse(keys) {
+            Some(seq) => seq,
+            None => {
+                errors.push(ConfigError::at_key(&key, "Invalid key sequence"));
+                continue;
+            }
+        };
+        match binding_from_toml(value, actions) {
+            Ok(binding) => {
+                key_map.insert(seq, binding);
+            }
+            Err(e) => errors.push(ConfigError::at_key(&key, &e)),
         }
     }
     key_map
 }
+
+/// Reads the `[options]` table, which sets the same options as `:set`:
+///
+/// ```toml
+/// [options]
+/// hidden = false
+/// sort = "mtime"
+/// timeoutlen = 500
+/// findignore = [".git", "target"]
+/// ```
+///
+/// An array of strings is joined with commas.
+pub fn process_config_options(toml_value: &Value, options: &mut Options, errors: &mut Vec<ConfigError>) {
+    let table = match toml_value.get("options") {
+        Some(Value::Table(table)) => table,
+        Some(_) => {
+            errors.push(ConfigError::at_key("options", "Expected a table"));
+            return;
+        }
+        None => return,
+    };
+    for (name, value) in table {
+        let key = key_path(&["options", name]);
+        let value = match value {
+            Value::String(s) => s.clone(),
+            Value::Integer(i) => i.to_string(),
+            Value::Boolean(b) => b.to_string(),
+            Value::Array(items) if items.iter().all(|x| x.is_str()) => {
+                let items: Vec<&str> = items.iter().filter_map(|x| x.as_str()).collect();
+                items.join(",")
+            }
+            _ => {
+                errors.push(ConfigError::at_key(
+                    &key,
+                    "Expected a string, integer, boolean or array of strings",
+                ));
+                continue;
+            }
+        };
+        if let Err(e) = options.set(&format!("{}={}", name, value)) {
+            errors.push(ConfigError::at_key(&key, &e));
+        }
+    }
+}
+
+/// Reads a value that may be given either as one string or as an array of them.
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(s)) => vec![s.clone()],
+        Some(Value::Array(a)) => a.iter().filter_map(|x| x.as_str()).map(String::from).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Reads the `[openers]` table. Returns `None` if there is none, so the
+/// default openers are used.
+pub fn process_config_openers(toml_value: &Value, errors: &mut Vec<ConfigError>) -> Option<Vec<Opener>> {
+    let table = match toml_value.get("openers")? {
+        Value::Table(table) => table,
+        _ => {
+            errors.push(ConfigError::at_key("openers", "Expected a table"));
+            return None;
+        }
+    };
+    let mut openers: Vec<Opener> = Vec::new();
+    for (name, rule) in table {
+        let key = key_path(&["openers", name]);
+        let command = match rule.get("command").and_then(|x| x.as_str()) {
+            Some(command) => command,
+            None => {
+                errors.push(ConfigError::at_key(&key, "Missing 'command'"));
+                continue;
+            }
+        };
+        let mut opener = Opener::new(name, command);
+        opener.exts = string_list(rule.get("ext"));
+        opener.mimes = string_list(rule.get("mime"));
+        for g in string_list(rule.get("glob")) {
+            match Glob::new(&g) {
+                Ok(glob) => opener.globs.push(glob),
+                Err(e) => errors.push(ConfigError::at_key(
+                    &key_path(&["openers", name, "glob"]),
+                    &format!("Invalid glob '{}': {}", g, e),
+                )),
+            }
+        }
+        opener.detached = rule.get("detached").and_then(|x| x.as_bool()).unwrap_or(false);
+        openers.push(opener);
+    }
+    Some(openers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_validated() {
+        let value: Value = r#"
+            [keys]
+            gw = { action = "cd", path = "~/work" }
+            ss = { action = "sort", by = "size", reverse = true }
+            gh = "cd_parent"
+            x1 = { action = "sort", by = "colour" }
+            x2 = { action = "cd", dir = "/tmp" }
+            x3 = "no_such_action"
+        "#
+        .parse()
+        .unwrap();
+        let mut errors = Vec::new();
+        let key_map = process_config_keys(&value, &get_actions(), &mut errors);
+        assert_eq!(key_map.len(), 3);
+        assert_eq!(errors.len(), 3);
+        let binding = &key_map[&KeySequence::parse("ss").unwrap()];
+        assert_eq!(binding.name, "sort");
+        assert_eq!(binding.args.str("by"), Some("size"));
+        assert!(binding.args.bool("reverse"));
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use termion::event::Key;
+
+use crate::zeuslib::actions::Binding;
+use crate::zeuslib::input::KeySequence;
+
+/// Key bindings stored by their keys, one node per key, so a partly typed
+/// sequence can be looked up without scanning every binding.
+#[derive(Clone, Default)]
+pub struct KeyTrie {
+    binding: Option<Binding>,
+    children: HashMap<Key, KeyTrie>,
+}
+
+/// What the keys typed so far mean.
+pub enum KeyLookup<'a> {
+    /// No binding starts with these keys.
+    None,
+    /// The keys start one or more longer bindings.
+    Prefix,
+    /// The keys are bound, but also start longer bindings.
+    Ambiguous(&'a Binding),
+    /// The keys are bound and start nothing longer.
+    Match(&'a Binding),
+}
+
+impl KeyTrie {
+    pub fn insert(&mut self, seq: &KeySequence, binding: Binding) {
+        let mut node = self;
+        for k in seq.iter() {
+            node = node.children.entry(*k).or_default();
+        }
+        node.binding = Some(binding);
+    }
+
+    /// Removes the binding for `seq`, dropping nodes left without bindings.
+    pub fn remove(&mut self, seq: &KeySequence) -> Option<Binding> {
+        let keys: Vec<Key> = seq.iter().copied().collect();
+        self.remove_keys(&keys)
+    }
+
+    fn remove_keys(&mut self, keys: &[Key]) -> Option<Binding> {
+        let (k, rest) = match keys.split_first() {
+            Some(x) => x,
+            None => return self.binding.take(),
+        };
+        let child = self.children.get_mut(k)?;
+        let removed = child.remove_keys(rest);
+        if child.binding.is_none() && child.children.is_empty() {
+            self.children.remove(k);
+        }
+        removed
+    }
+
+    /// The node reached by typing `seq`.
+    pub fn node(&self, seq: &KeySequence) -> Option<&KeyTrie> {
+        let mut node = self;
+        for k in seq.iter() {
+            node = node.children.get(k)?;
+        }
+        Some(node)
+    }
+
+    /// Every binding that continues `seq`, with the keys left to type.
+    pub fn continuations(&self, seq: &KeySequence) -> Vec<(KeySequence, &Binding)> {
+        let mut found: Vec<(KeySequence, &Binding)> = Vec::new();
+        if let Some(node) = self.node(seq) {
+            node.collect(&mut Vec::new(), &mut found);
+        }
+        found
+    }
+
+    fn collect<'a>(&'a self, keys: &mut Vec<Key>, found: &mut Vec<(KeySequence, &'a Binding)>) {
+        for (k, child) in self.children.iter() {
+            keys.push(*k);
+            if let Some(binding) = &child.binding {
+                found.push((KeySequence::from_keys(keys), binding));
+            }
+            child.collect(keys, found);
+            keys.pop();
+        }
+    }
+
+    pub fn lookup(&self, seq: &KeySequence) -> KeyLookup<'_> {
+        match self.node(seq) {
+            None => KeyLookup::None,
+            Some(node) => match (&node.binding, node.children.is_empty()) {
+                (Some(binding), true) => KeyLookup::Match(binding),
+                (Some(binding), false) => KeyLookup::Ambiguous(binding),
+                (None, false) => KeyLookup::Prefix,
+                (None, true) => KeyLookup::None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zeuslib::actions::{get_actions, ActionArgs};
+
+    #[test]
+    fn lookup_prefixes() {
+        let actions = get_actions();
+        let bind = |name: &str| Binding::new(name, &actions[name], ActionArgs::default());
+        let seq = |s| KeySequence::parse(s).unwrap();
+        let mut trie = KeyTrie::default();
+        trie.insert(&seq("g"), bind("cd_parent"));
+        trie.insert(&seq("gg"), bind("move_up"));
+        trie.insert(&seq("dd"), bind("trash"));
+
+        assert!(matches!(trie.lookup(&seq("g")), KeyLookup::Ambiguous(b) if b.name == "cd_parent"));
+        assert!(matches!(trie.lookup(&seq("gg")), KeyLookup::Match(b) if b.name == "move_up"));
+        assert!(matches!(trie.lookup(&seq("d")), KeyLookup::Prefix));
+        assert!(matches!(trie.lookup(&seq("dx")), KeyLookup::None));
+
+        let mut next: Vec<String> = trie
+            .continuations(&seq("g"))
+            .iter()
+            .map(|(k, b)| format!("{} {}", k, b.name))
+            .collect();
+        next.sort();
+        assert_eq!(next, vec!["g move_up"]);
+        assert_eq!(trie.continuations(&KeySequence::default()).len(), 3);
+
+        assert!(trie.remove(&seq("dd")).is_some());
+        assert!(matches!(trie.lookup(&seq("d")), KeyLookup::None));
+        assert!(trie.remove(&seq("gg")).is_some());
+        assert!(matches!(trie.lookup(&seq("g")), KeyLookup::Match(_)));
+        assert!(trie.remove(&seq("x")).is_none());
+    }
+}
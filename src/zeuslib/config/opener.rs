@@ -0,0 +1,155 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::zeuslib::utils::fs::mime::mime_matches;
+use crate::zeuslib::utils::glob::Glob;
+use crate::zeuslib::utils::shellwords;
+
+/// A rule from the `[openers]` table saying which program opens which files.
+///
+/// A rule matches a file if any of its extensions, globs or MIME patterns
+/// match. A rule without any of them matches every file, but `openers_for`
+/// only falls back to it when no other rule matches.
+#[derive(Debug, Clone)]
+pub struct Opener {
+    pub name: String,
+    pub exts: Vec<String>,
+    pub globs: Vec<Glob>,
+    pub mimes: Vec<String>,
+    /// Command line with placeholders: `%f` is the selected file, `%F` all
+    /// marked files as separate arguments, `%d` the current directory.
+    pub command: String,
+    /// Run in the background instead of handing the terminal over.
+    pub detached: bool,
+}
+
+impl Opener {
+    pub fn new(name: &str, command: &str) -> Self {
+        Self {
+            name: String::from(name),
+            exts: Vec::new(),
+            globs: Vec::new(),
+            mimes: Vec::new(),
+            command: String::from(command),
+            detached: false,
+        }
+    }
+
+    pub fn is_catch_all(&self) -> bool {
+        self.exts.is_empty() && self.globs.is_empty() && self.mimes.is_empty()
+    }
+
+    pub fn matches(&self, path: &Path, mime: &str) -> bool {
+        if self.is_catch_all() {
+            return true;
+        }
+        let name = path
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some(ext) = path.extension() {
+            let ext = ext.to_string_lossy().to_lowercase();
+            if self.exts.iter().any(|x| x.to_lowercase() == ext) {
+                return true;
+            }
+        }
+        self.globs.iter().any(|g| g.is_match(&name))
+            || self.mimes.iter().any(|m| mime_matches(m, mime))
+    }
+
+    /// Builds the argument vector for opening `paths`, the first of which is
+    /// the selected one. Paths are appended if the command has no placeholder.
+    ///
+    /// Paths are passed on as they are, whether or not they are UTF-8.
+    pub fn command_line(
+        &self,
+        paths: &[PathBuf],
+        dir: Option<&Path>,
+    ) -> Result<Vec<OsString>, String> {
+        let words = shellwords::split(&shellwords::expand_vars(&self.command))?;
+        if words.is_empty() {
+            return Err(format!("Opener '{}' has an empty command", self.name));
+        }
+        let first = paths.first().map(|p| p.as_os_str()).unwrap_or_default();
+        let dir = dir.map(|d| d.as_os_str()).unwrap_or_default();
+        let mut argv: Vec<OsString> = Vec::new();
+        let mut used_placeholder = false;
+        for word in words {
+            if word == "%F" {
+                used_placeholder = true;
+                argv.extend(paths.iter().map(|p| p.as_os_str().to_os_string()));
+                continue;
+            }
+            let mut out = OsString::new();
+            let mut chars = word.chars().peekable();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    out.push(c.encode_utf8(&mut [0; 4]));
+                    continue;
+                }
+                match chars.next() {
+                    Some('f') => {
+                        used_placeholder = true;
+                        out.push(first);
+                    }
+                    Some('d') => out.push(dir),
+                    Some(c) => {
+                        out.push("%");
+                        if c != '%' {
+                            out.push(c.encode_utf8(&mut [0; 4]));
+                        }
+                    }
+                    None => out.push("%"),
+                }
+            }
+            argv.push(out);
+        }
+        if !used_placeholder {
+            argv.extend(paths.iter().map(|p| p.as_os_str().to_os_string()));
+        }
+        Ok(argv)
+    }
+}
+
+/// The openers for the file at `path`: the rules that match it, or the
+/// catch-all rules if none do. With `all`, the catch-all rules are always
+/// listed, after the others.
+pub fn openers_for(openers: &[Opener], path: &Path, mime: &str, all: bool) -> Vec<Opener> {
+    let (fallbacks, mut matching): (Vec<&Opener>, Vec<&Opener>) = openers
+        .iter()
+        .filter(|o| o.matches(path, mime))
+        .partition(|o| o.is_catch_all());
+    if all || matching.is_empty() {
+        matching.extend(fallbacks);
+    }
+    matching.into_iter().cloned().collect()
+}
+
+/// Openers used when the config file has no `[openers]` table: text in the
+/// editor, everything else with `xdg-open`.
+pub fn default_openers() -> Vec<Opener> {
+    let mut editor = Opener::new("editor", "${VISUAL:-${EDITOR:-vi}} %F");
+    editor.mimes.push(String::from("text/*"));
+    let mut xdg = Opener::new("xdg-open", "xdg-open %f");
+    xdg.detached = true;
+    vec![editor, xdg]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catch_all_openers_are_fallbacks() {
+        let openers = default_openers();
+        let names = |path: &str, mime: &str, all: bool| -> Vec<String> {
+            openers_for(&openers, Path::new(path), mime, all)
+                .into_iter()
+                .map(|o| o.name)
+                .collect()
+        };
+        assert_eq!(names("a.txt", "text/plain", false), vec!["editor"]);
+        assert_eq!(names("a.txt", "text/plain", true), vec!["editor", "xdg-open"]);
+        assert_eq!(names("a.png", "image/png", false), vec!["xdg-open"]);
+    }
+}
@@ -2,25 +2,42 @@ use std::io;
 use std::sync::mpsc;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, Mutex,
 };
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use termion::event::Key;
 use termion::input::TermRead;
 
+use crate::zeuslib::finder::FindEvent;
+use crate::zeuslib::jobs::JobEvent;
+use crate::zeuslib::listing::ListEvent;
+use crate::zeuslib::watcher::WatchEvent;
+
 pub enum Event<I> {
     Input(I),
     Tick,
+    Job(JobEvent),
+    Find(FindEvent),
+    List(ListEvent),
+    Watch(WatchEvent),
 }
 
+pub type EventSender = mpsc::Sender<Event<Key>>;
+
+/// How long the input thread waits for stdin before checking whether it has
+/// been paused.
+const INPUT_POLL_MS: libc::c_int = 50;
+
 /// A small event handler that wrap termion input and tick events. Each event
 /// type is handled in its own thread and returned to a common `Receiver`
 pub struct Events {
     rx: mpsc::Receiver<Event<Key>>,
+    tx: EventSender,
     _input_handle: thread::JoinHandle<()>,
     ignore_exit_key: Arc<AtomicBool>,
+    input_paused: Arc<Mutex<bool>>,
     _tick_handle: thread::JoinHandle<()>,
 }
 
@@ -39,6 +56,12 @@ impl Default for Config {
     }
 }
 
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Events {
     pub fn new() -> Events {
         Events::with_config(Config::default())
@@ -47,13 +70,42 @@ impl Events {
     pub fn with_config(config: Config) -> Events {
         let (tx, rx) = mpsc::channel();
         let ignore_exit_key = Arc::new(AtomicBool::new(false));
+        let input_paused = Arc::new(Mutex::new(false));
         let _input_handle = {
             let tx = tx.clone();
             let ignore_exit_key = ignore_exit_key.clone();
+            let input_paused = input_paused.clone();
             thread::spawn(move || {
-                let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    if let Ok(key) = evt {
+                let mut buf = [0u8; 1024];
+                // Bytes read but not parsed yet, see `incomplete_tail`
+                let mut pending: Vec<u8> = Vec::new();
+                loop {
+                    // stdin is only touched while holding the lock, so once
+                    // `pause_input` returns no keys can be stolen from a child
+                    // process that owns the terminal.
+                    let n = {
+                        let paused = input_paused.lock().unwrap();
+                        if *paused {
+                            None
+                        } else {
+                            Some(read_stdin(&mut buf))
+                        }
+                    };
+                    let n = match n {
+                        Some(Ok(n)) => n,
+                        // stdin was closed, there will be no more input
+                        Some(Err(_)) => return,
+                        None => {
+                            thread::sleep(Duration::from_millis(INPUT_POLL_MS as u64));
+                            continue;
+                        }
+                    };
+                    pending.extend_from_slice(&buf[..n]);
+                    // A key cut off at the end of a read waits for the rest,
+                    // unless no more input came, as after a lone Esc
+                    let keep = if n > 0 { incomplete_tail(&pending) } else { 0 };
+                    let ready: Vec<u8> = pending.drain(..pending.len() - keep).collect();
+                    for key in ready.keys().flatten() {
                         if let Err(err) = tx.send(Event::Input(key)) {
                             eprintln!("{}", err);
                             return;
@@ -66,6 +118,7 @@ impl Events {
             })
         };
         let _tick_handle = {
+            let tx = tx.clone();
             thread::spawn(move || loop {
                 if tx.send(Event::Tick).is_err() {
                     break;
@@ -75,7 +128,9 @@ impl Events {
         };
         Events {
             rx,
+            tx,
             ignore_exit_key,
+            input_paused,
             _input_handle,
             _tick_handle,
         }
@@ -85,6 +140,38 @@ impl Events {
         self.rx.recv()
     }
 
+    /// Like `next`, but gives a `Tick` at `deadline` if nothing else has
+    /// come by then, so timeouts shorter than the tick rate are kept.
+    pub fn next_until(&self, deadline: Option<Instant>) -> Result<Event<Key>, mpsc::RecvError> {
+        let deadline = match deadline {
+            Some(deadline) => deadline,
+            None => return self.next(),
+        };
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.rx.recv_timeout(timeout) {
+            Ok(evt) => Ok(evt),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(Event::Tick),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(mpsc::RecvError),
+        }
+    }
+
+    /// A sender that can be handed to worker threads to feed events into this
+    /// handler's queue.
+    pub fn sender(&self) -> EventSender {
+        self.tx.clone()
+    }
+
+    /// Stops reading stdin, e.g. while an external program owns the terminal.
+    ///
+    /// Blocks until the input thread has finished any read in progress.
+    pub fn pause_input(&self) {
+        *self.input_paused.lock().unwrap() = true;
+    }
+
+    pub fn resume_input(&self) {
+        *self.input_paused.lock().unwrap() = false;
+    }
+
     pub fn disable_exit_key(&mut self) {
         self.ignore_exit_key.store(true, Ordering::Relaxed);
     }
@@ -94,6 +181,99 @@ impl Events {
     }
 }
 
+/// Waits up to `INPUT_POLL_MS` for stdin to become readable and reads what is
+/// available. Returns the number of bytes read, `0` if there was nothing.
+fn read_stdin(buf: &mut [u8]) -> io::Result<usize> {
+    let mut fds = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let ready = unsafe { libc::poll(&mut fds, 1, INPUT_POLL_MS) };
+    if ready <= 0 || fds.revents & (libc::POLLIN | libc::POLLHUP) == 0 {
+        return Ok(0);
+    }
+    let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+    match n {
+        n if n > 0 => Ok(n as usize),
+        0 => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stdin closed")),
+        _ => {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                Ok(0)
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
+/// How many bytes at the end of `bytes` may be the start of a key that is
+/// still being received: an escape sequence or a UTF-8 character split
+/// between two reads.
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    // Escape sequences are short; an Esc further back has been completed
+    let window = &bytes[bytes.len().saturating_sub(16)..];
+    if let Some(i) = window.iter().rposition(|&b| b == 0x1b) {
+        let tail = &window[i..];
+        let complete = match tail.get(1) {
+            None => false,
+            // CSI, ending in a byte from `@` to `~`; X10 mouse reports
+            // have three more bytes after `M`
+            Some(b'[') => match tail.get(2) {
+                Some(b'M') => tail.len() >= 6,
+                _ => tail.iter().skip(2).any(|b| (0x40..=0x7e).contains(b)),
+            },
+            Some(b'O') => tail.len() >= 3,
+            Some(_) => true,
+        };
+        if !complete {
+            return tail.len();
+        }
+    }
+    // The lead byte of the last character tells how long it should be
+    let start = bytes.len().saturating_sub(4);
+    for (i, &b) in bytes.iter().enumerate().skip(start).rev() {
+        let len = match b {
+            0x80..=0xbf => continue,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        return if bytes.len() - i < len { bytes.len() - i } else { 0 };
+    }
+    0
+}
+
 pub mod procevent;
 pub mod loopaction;
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_keys_wait_for_the_rest() {
+        assert_eq!(incomplete_tail(b"ab"), 0);
+        assert_eq!(incomplete_tail(b"a\x1b"), 1);
+        assert_eq!(incomplete_tail(b"a\x1b["), 2);
+        assert_eq!(incomplete_tail(b"a\x1b[1;5"), 5);
+        assert_eq!(incomplete_tail(b"a\x1b[1;5A"), 0);
+        assert_eq!(incomplete_tail(b"\x1bO"), 2);
+        assert_eq!(incomplete_tail(b"\x1b[M a"), 5);
+        assert_eq!(incomplete_tail(b"\x1bj"), 0);
+        assert_eq!(incomplete_tail("é".as_bytes()), 0);
+        assert_eq!(incomplete_tail(&"aé".as_bytes()[..2]), 1);
+        assert_eq!(incomplete_tail(&"€".as_bytes()[..2]), 2);
+
+        // The parts parse as the whole would
+        let whole = b"j\x1b[Bk";
+        let mut pending = whole[..3].to_vec();
+        let keep = incomplete_tail(&pending);
+        let first: Vec<u8> = pending.drain(..pending.len() - keep).collect();
+        pending.extend_from_slice(&whole[3..]);
+        let keys: Vec<Key> = first.keys().chain(pending.keys()).flatten().collect();
+        assert_eq!(keys, vec![Key::Char('j'), Key::Down, Key::Char('k')]);
+    }
+}
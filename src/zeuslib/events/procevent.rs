@@ -1,55 +1,331 @@
+use crate::zeuslib::commands::{complete, run_command};
 use crate::zeuslib::state::State;
-use crate::zeuslib::config::Config;
+use crate::zeuslib::config::{Config, KeyLookup};
 use crate::zeuslib::events::loopaction::EventLoopAction;
+use crate::zeuslib::finder::{FindEvent, FindUpdate};
+use crate::zeuslib::input::KeySequence;
+use crate::zeuslib::jobs::{JobEvent, JobOp};
+use crate::zeuslib::listing::ListEvent;
+use crate::zeuslib::watcher::WatchEvent;
+use crate::zeuslib::ui::cmdline::CommandLineOutcome;
+use crate::zeuslib::ui::dialog::{Dialog, DialogOutcome};
+use crate::zeuslib::ui::finder::FinderOutcome;
 
 
 use std::time::{Duration, Instant};
 use termion::event::{Key};
 
-lazy_static! {
-    static ref KEY_TIMEOUT: Duration = Duration::from_millis(1000);
+/// When the keys typed so far stop waiting for a longer sequence, if they
+/// are; the event loop should not wait for events past it.
+pub fn key_deadline(state: &State) -> Option<Instant> {
+    let timeout = Duration::from_millis(state.options.key_timeout);
+    state.last_key_time.map(|kt| kt + timeout)
 }
 
-pub fn handle_tick(state: &mut State) {
-    let now = Instant::now();
-    match state.last_key_time {
-        Some(kt) => {
-            let elapsed = now.duration_since(kt);
-            if elapsed >= *KEY_TIMEOUT {
-                state.key_seq.clear();
+/// Runs the binding of a sequence that was waiting for a longer one once the
+/// key timeout has passed without another key.
+pub fn handle_tick(state: &mut State, config: &Config) -> EventLoopAction {
+    refresh_changed_dirs(state);
+    match key_deadline(state) {
+        Some(deadline) if Instant::now() >= deadline => {}
+        _ => return EventLoopAction::ContinueLoop,
+    }
+    state.last_key_time = None;
+    let (count, keys) = state.key_seq.split_count();
+    state.key_seq.clear();
+    match config.key_trie.lookup(&keys) {
+        KeyLookup::Ambiguous(binding) => binding.run(state, count),
+        _ => EventLoopAction::ContinueLoop,
+    }
+}
+
+/// Sends a key to the open dialog, running its callback once it is answered.
+fn handle_dialog_key(state: &mut State, dialog: Dialog, k: Key) -> EventLoopAction {
+    match dialog.handle_key(k) {
+        DialogOutcome::Pending(dialog) => {
+            state.dialog = Some(dialog);
+            EventLoopAction::ContinueLoop
+        }
+        DialogOutcome::Submit(callback) => callback(state),
+        DialogOutcome::Cancel => EventLoopAction::ContinueLoop,
+    }
+}
+
+/// Sends a key to the open command line, running the line once it is entered.
+fn handle_cmdline_key(state: &mut State, config: &mut Config, k: Key) -> EventLoopAction {
+    let dir = state.get_current_tab().dir.clone();
+    let outcome = {
+        let config = &*config;
+        let complete = |line: &str| complete(config, dir.as_deref(), line);
+        state.cmdline.handle_key(k, &complete)
+    };
+    match outcome {
+        CommandLineOutcome::Submit(line) => {
+            state.cmdline.push_history(&line);
+            run_command(state, config, &line)
+        }
+        CommandLineOutcome::Pending | CommandLineOutcome::Cancel => EventLoopAction::ContinueLoop,
+    }
+}
+
+/// Puts the cursor back where a search started.
+fn return_to_origin(state: &mut State, origin: usize) {
+    if let Ok(Some(panel)) = state.get_current_panel_mut() {
+        panel.borrow_mut().select(origin);
+    }
+    state.get_current_tab_mut().update_preview();
+}
+
+/// Sends a key to the open search prompt, moving the cursor to the first
+/// match as the pattern is typed. Cancelling goes back to where the search
+/// started.
+fn handle_search_key(state: &mut State, k: Key) -> EventLoopAction {
+    let origin = state.search_origin.unwrap_or(0);
+    let outcome = state.search_line.handle_key(k, &|_| Vec::new());
+    match outcome {
+        CommandLineOutcome::Pending => {
+            let pattern = state.search_line.text().unwrap_or_default();
+            if pattern.is_empty() {
+                return_to_origin(state, origin);
+                return EventLoopAction::ContinueLoop;
+            }
+            // A regex is often invalid while it is being typed
+            if let Ok(matcher) = state.options.search_matcher(&pattern) {
+                if !state.jump_to_match(&matcher, origin, true, true) {
+                    return_to_origin(state, origin);
+                }
+                state.set_highlight(Some(&matcher));
             }
         }
-        _ => {}
+        CommandLineOutcome::Submit(pattern) => {
+            state.search_line.push_history(&pattern);
+            // An empty pattern repeats the last search
+            let matcher = match (pattern.is_empty(), &state.search) {
+                (true, Some(last)) => Ok(last.clone()),
+                _ => state.options.search_matcher(&pattern),
+            };
+            match matcher {
+                Ok(matcher) => {
+                    if !state.jump_to_match(&matcher, origin, true, true) {
+                        state.message = format!("Pattern not found: {}", matcher.as_str());
+                    }
+                    state.set_highlight(Some(&matcher));
+                    state.search = Some(matcher);
+                }
+                Err(e) => state.message = e,
+            }
+        }
+        CommandLineOutcome::Cancel => {
+            return_to_origin(state, origin);
+            let last = state.search.clone();
+            state.set_highlight(last.as_ref());
+        }
     }
+    EventLoopAction::ContinueLoop
 }
 
-fn handle_key_event(mut state: &mut State, config: &Config, k: Key) -> EventLoopAction {
-    let now = Instant::now();
+/// Sends a key to the open filter prompt, narrowing the listing as the
+/// pattern is typed. An empty pattern clears the filter; cancelling puts back
+/// the one there was before.
+fn handle_filter_key(state: &mut State, k: Key) -> EventLoopAction {
+    let outcome = state.filter_line.handle_key(k, &|_| Vec::new());
+    match outcome {
+        CommandLineOutcome::Pending => {
+            let pattern = state.filter_line.text().unwrap_or_default();
+            if pattern.is_empty() {
+                state.get_current_tab_mut().set_filter(None);
+            } else if let Ok(matcher) = state.options.filter_matcher(&pattern) {
+                state.get_current_tab_mut().set_filter(Some(matcher));
+            }
+        }
+        CommandLineOutcome::Submit(pattern) => {
+            state.filter_line.push_history(&pattern);
+            if pattern.is_empty() {
+                state.get_current_tab_mut().set_filter(None);
+            } else {
+                match state.options.filter_matcher(&pattern) {
+                    Ok(matcher) => state.get_current_tab_mut().set_filter(Some(matcher)),
+                    Err(e) => {
+                        state.message = e;
+                        let origin = state.filter_origin.take();
+                        state.get_current_tab_mut().set_filter(origin);
+                    }
+                }
+            }
+        }
+        CommandLineOutcome::Cancel => {
+            let origin = state.filter_origin.take();
+            state.get_current_tab_mut().set_filter(origin);
+        }
+    }
+    EventLoopAction::ContinueLoop
+}
+
+/// Sends a key to the open finder. Picking a path goes to its directory
+/// with the cursor on it.
+fn handle_finder_key(state: &mut State, k: Key) -> EventLoopAction {
+    let outcome = match &mut state.find {
+        Some(finder) => finder.handle_key(k),
+        None => return EventLoopAction::ContinueLoop,
+    };
+    match outcome {
+        FinderOutcome::Pending => {}
+        FinderOutcome::Cancel => {
+            state.finder.stop();
+            state.find = None;
+        }
+        FinderOutcome::Select(path) => {
+            state.finder.stop();
+            state.find = None;
+            let tab = state.get_current_tab_mut();
+            tab.cd(path.parent().map(|x| x.to_path_buf()));
+            tab.select_path(&path);
+        }
+    }
+    EventLoopAction::ContinueLoop
+}
+
+fn handle_key_event(state: &mut State, config: &mut Config, k: Key) -> EventLoopAction {
+    if let Some(dialog) = state.dialog.take() {
+        return handle_dialog_key(state, dialog, k);
+    }
+    if state.cmdline.is_active() {
+        return handle_cmdline_key(state, config, k);
+    }
+    if state.search_line.is_active() {
+        return handle_search_key(state, k);
+    }
+    if state.filter_line.is_active() {
+        return handle_filter_key(state, k);
+    }
+    if state.find.is_some() {
+        return handle_finder_key(state, k);
+    }
+    if let Some(help) = &mut state.help {
+        if !help.handle_key(k) {
+            state.help = None;
+        }
+        return EventLoopAction::ContinueLoop;
+    }
+
     if k == Key::Esc {
         state.key_seq.clear();
-        state.last_key_time = Some(now);
+        state.last_key_time = None;
         return EventLoopAction::ContinueLoop;
     }
-    state.last_key_time = Some(now);
+    // The message is about the last command; a new one replaces it
+    if state.key_seq.is_empty() {
+        state.message.clear();
+    }
     state.key_seq.push(k);
+    dispatch_keys(state, config)
+}
 
-    let key_map = &config.key_map;
-    
-    for kv in key_map.iter() {
-        let seq = kv.0;
-        let action = kv.1;
-        if state.key_seq == *seq {
-            let r = action(&mut state);
+/// Runs the binding for the pending keys once they can only mean one thing,
+/// passing it the count typed before them.
+///
+/// Keys that start a longer binding wait for more keys, or for the timeout
+/// in `handle_tick`; a count alone waits for keys without a timeout. On a
+/// dead end the pending keys are dropped at once: if the keys before the last
+/// one were bound they run first, then the last key starts a new sequence.
+fn dispatch_keys(state: &mut State, config: &Config) -> EventLoopAction {
+    let (count, seq) = state.key_seq.split_count();
+    if seq.is_empty() {
+        return EventLoopAction::ContinueLoop;
+    }
+    match config.key_trie.lookup(&seq) {
+        KeyLookup::Match(binding) => {
             state.key_seq.clear();
-            if r == EventLoopAction::QuitLoop {
-                return r;
+            state.last_key_time = None;
+            binding.run(state, count)
+        }
+        KeyLookup::Prefix | KeyLookup::Ambiguous(_) => {
+            state.last_key_time = Some(Instant::now());
+            EventLoopAction::ContinueLoop
+        }
+        KeyLookup::None => {
+            let mut keys: Vec<Key> = seq.iter().copied().collect();
+            state.key_seq.clear();
+            state.last_key_time = None;
+            let last = match keys.pop() {
+                Some(last) if !keys.is_empty() => last,
+                _ => return EventLoopAction::ContinueLoop,
+            };
+            let prefix = KeySequence::from_keys(&keys);
+            if let KeyLookup::Ambiguous(binding) = config.key_trie.lookup(&prefix) {
+                if binding.run(state, count) == EventLoopAction::QuitLoop {
+                    return EventLoopAction::QuitLoop;
+                }
             }
+            state.key_seq.push(last);
+            dispatch_keys(state, config)
         }
     }
-    
-    EventLoopAction::ContinueLoop
 }
 
-pub fn handle_input(state: &mut State, config: &Config, key: Key) -> EventLoopAction {
+pub fn handle_input(state: &mut State, config: &mut Config, key: Key) -> EventLoopAction {
     handle_key_event(state, config, key)
 }
+
+/// Passes what the finder's walk found on to the open finder.
+pub fn handle_find_event(state: &mut State, evt: FindEvent) {
+    if !state.finder.is_current(evt.id) {
+        return;
+    }
+    if let Some(finder) = &mut state.find {
+        match evt.update {
+            FindUpdate::Found(paths) => finder.add(paths),
+            FindUpdate::Finished => finder.finish(),
+        }
+    }
+}
+
+/// Passes entries read in the background to the file list that asked for
+/// them, updating the preview if the cursor moved onto another entry.
+pub fn handle_list_event(state: &mut State, evt: ListEvent) {
+    for tab in state.tabs.iter_mut() {
+        if let Some((list, is_main)) = tab.find_list(evt.list_id) {
+            let moved = list.borrow_mut().receive(evt.load_id, evt.update);
+            if moved && is_main {
+                tab.update_preview();
+            }
+            return;
+        }
+    }
+}
+
+/// Notes that a watched directory changed; its listings are refreshed by
+/// `handle_tick` once it has settled.
+pub fn handle_watch_event(state: &mut State, evt: WatchEvent) {
+    let now = Instant::now();
+    match evt {
+        WatchEvent::Changed(dir) => state.watcher.changes.push(dir, now),
+        WatchEvent::Overflow => {
+            for dir in state.watcher.watched() {
+                state.watcher.changes.push(dir, now);
+            }
+        }
+    }
+}
+
+/// Refreshes the listings of watched directories that have settled.
+fn refresh_changed_dirs(state: &mut State) {
+    let delay = Duration::from_millis(state.options.watch_delay);
+    let dirs = state.watcher.changes.take_due(delay, Instant::now());
+    if !dirs.is_empty() {
+        state.refresh_dirs(&dirs);
+    }
+}
+
+pub fn handle_job_event(state: &mut State, evt: JobEvent) {
+    if let Some((job, r)) = state.jobs.handle_event(evt) {
+        if job.op == JobOp::Move {
+            state.register.finish_move(&job.sources, r.is_ok());
+        }
+        state.message = match r {
+            Ok(()) => format!("{}: done", job.description),
+            Err(e) => format!("{}: {}", job.description, e),
+        };
+        state.refresh();
+    }
+}
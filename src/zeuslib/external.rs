@@ -0,0 +1,165 @@
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::rc::Rc;
+
+use termion::screen::{ToAlternateScreen, ToMainScreen};
+use tui::Terminal;
+
+use crate::zeuslib::events::loopaction::EventLoopAction;
+use crate::zeuslib::events::Events;
+use crate::zeuslib::state::State;
+use crate::zeuslib::Backend;
+
+pub type ExitCallback = Rc<dyn Fn(&mut State, io::Result<ExitStatus>) -> EventLoopAction>;
+
+/// A program that needs the terminal to itself, like an editor.
+///
+/// Actions put one into `State::external_command`; the main loop then hands
+/// the terminal over with `Suspender::run` and afterwards calls `on_exit`.
+pub struct ExternalCommand {
+    pub program: OsString,
+    pub args: Vec<OsString>,
+    pub cwd: Option<PathBuf>,
+    pub on_exit: Option<ExitCallback>,
+}
+
+impl ExternalCommand {
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
+        Self {
+            program: program.as_ref().to_os_string(),
+            args: Vec::new(),
+            cwd: None,
+            on_exit: None,
+        }
+    }
+
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    pub fn current_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.cwd = dir;
+        self
+    }
+
+    pub fn on_exit(mut self, on_exit: ExitCallback) -> Self {
+        self.on_exit = Some(on_exit);
+        self
+    }
+}
+
+/// Starts `program` in the background with its output discarded.
+///
+/// The child is reaped on a separate thread so it does not linger as a zombie.
+pub fn spawn_detached(program: &OsStr, args: &[OsString], cwd: Option<&PathBuf>) -> io::Result<()> {
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    let mut child = command.spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// The user's preferred editor from `$VISUAL` or `$EDITOR`, `vi` otherwise.
+pub fn editor() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"))
+}
+
+/// The user's preferred pager from `$PAGER`, `less` otherwise.
+pub fn pager() -> String {
+    std::env::var("PAGER").unwrap_or_else(|_| String::from("less"))
+}
+
+/// The user's login shell from `$SHELL`, `sh` otherwise.
+pub fn shell() -> String {
+    std::env::var("SHELL").unwrap_or_else(|_| String::from("sh"))
+}
+
+// Mouse reporting modes enabled by `termion::input::MouseTerminal`
+const ENTER_MOUSE_SEQUENCE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+const EXIT_MOUSE_SEQUENCE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+
+fn get_mode() -> io::Result<libc::termios> {
+    let mut mode: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut mode) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(mode)
+}
+
+fn set_mode(mode: &libc::termios) -> io::Result<()> {
+    if unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, mode) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Remembers the terminal mode from before the UI started, so it can be
+/// restored while an external program runs.
+pub struct Suspender {
+    cooked: libc::termios,
+}
+
+impl Suspender {
+    /// Must be called before the terminal is switched into raw mode.
+    pub fn new() -> io::Result<Self> {
+        Ok(Self { cooked: get_mode()? })
+    }
+
+    /// Gives the terminal to `cmd` until it exits, then takes it back.
+    ///
+    /// The stdin reader of `events` is paused, the alternate screen, mouse
+    /// reporting and raw mode are switched off, and all of it is restored
+    /// afterwards, even if the program could not be started. The next draw is
+    /// a full redraw.
+    pub fn run(
+        &self,
+        terminal: &mut Terminal<Backend>,
+        events: &Events,
+        cmd: &ExternalCommand,
+    ) -> io::Result<ExitStatus> {
+        events.pause_input();
+        let raw = get_mode();
+        let status = self.leave(terminal).and_then(|_| {
+            let mut command = Command::new(&cmd.program);
+            command.args(&cmd.args);
+            if let Some(cwd) = &cmd.cwd {
+                command.current_dir(cwd);
+            }
+            command.status()
+        });
+        let restored = Self::enter(terminal, raw);
+        events.resume_input();
+        restored?;
+        status
+    }
+
+    fn leave(&self, terminal: &mut Terminal<Backend>) -> io::Result<()> {
+        let out = terminal.backend_mut();
+        write!(out, "{}{}{}", EXIT_MOUSE_SEQUENCE, ToMainScreen, termion::cursor::Show)?;
+        out.flush()?;
+        set_mode(&self.cooked)
+    }
+
+    fn enter(terminal: &mut Terminal<Backend>, raw: io::Result<libc::termios>) -> io::Result<()> {
+        set_mode(&raw?)?;
+        {
+            let out = terminal.backend_mut();
+            write!(out, "{}{}", ToAlternateScreen, ENTER_MOUSE_SEQUENCE)?;
+            out.flush()?;
+        }
+        terminal.hide_cursor()?;
+        terminal.clear()
+    }
+}
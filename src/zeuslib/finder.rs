@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::zeuslib::events::{Event, EventSender};
+use crate::zeuslib::utils::fs::walk::{walk, WalkOptions};
+
+/// How often a walk sends what it has found so far.
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A report sent from a walking thread back to the event loop.
+#[derive(Debug, Clone)]
+pub enum FindUpdate {
+    Found(Vec<PathBuf>),
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+pub struct FindEvent {
+    pub id: usize,
+    pub update: FindUpdate,
+}
+
+/// Walks directory trees for the fuzzy finder on a worker thread, one walk
+/// at a time.
+#[derive(Default)]
+pub struct Finder {
+    next_id: usize,
+    sender: Option<EventSender>,
+    /// The walk in progress and the flag that stops it.
+    current: Option<(usize, Arc<AtomicBool>)>,
+}
+
+impl Finder {
+    pub fn set_sender(&mut self, sender: EventSender) {
+        self.sender = Some(sender);
+    }
+
+    /// Starts listing everything below `root`, stopping any walk already in
+    /// progress.
+    pub fn start(&mut self, root: PathBuf, options: WalkOptions) -> usize {
+        self.stop();
+        let id = self.next_id;
+        self.next_id += 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.current = Some((id, cancelled.clone()));
+
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let report = |update: FindUpdate| match &sender {
+                Some(sender) => sender.send(Event::Find(FindEvent { id, update })).is_ok(),
+                None => false,
+            };
+            let mut batch: Vec<PathBuf> = Vec::new();
+            let mut last_report = Instant::now();
+            walk(&root, &options, &mut |path| {
+                if cancelled.load(Ordering::Relaxed) {
+                    return false;
+                }
+                batch.push(path);
+                if last_report.elapsed() >= BATCH_INTERVAL {
+                    last_report = Instant::now();
+                    return report(FindUpdate::Found(std::mem::take(&mut batch)));
+                }
+                true
+            });
+            if !cancelled.load(Ordering::Relaxed) {
+                report(FindUpdate::Found(batch));
+                report(FindUpdate::Finished);
+            }
+        });
+        id
+    }
+
+    /// Stops the walk in progress, if any. Reports it already sent are
+    /// dropped by `is_current`.
+    pub fn stop(&mut self) {
+        if let Some((_, cancelled)) = self.current.take() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether reports from walk `id` are still wanted.
+    pub fn is_current(&self, id: usize) -> bool {
+        matches!(&self.current, Some((current, _)) if *current == id)
+    }
+}
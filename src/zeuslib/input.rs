@@ -1,8 +1,134 @@
 extern crate termion;
 
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use termion::event::Key;
 
+// Key notation
+//
+// A key is either a single character, or a name in angle brackets: `<CR>`,
+// `<Down>`, `<F5>`, `<Space>`, `<lt>` for a literal `<`, and `<C-a>` or
+// `<M-a>` for a character with Ctrl or Alt; after a modifier `gt` stands for
+// `>`, as in `<M-gt>`. Names are case-insensitive.
+// `format_key` always produces the first spelling listed below, so parsing
+// and formatting round-trip.
+
+/// Names of keys that are not plain characters, canonical spelling first.
+const NAMED_KEYS: &[(&[&str], Key)] = &[
+    (&["CR", "Enter", "Return"], Key::Char('\n')),
+    (&["Tab"], Key::Char('\t')),
+    (&["Space"], Key::Char(' ')),
+    (&["lt"], Key::Char('<')),
+    (&["BS", "Backspace"], Key::Backspace),
+    (&["Esc", "Escape"], Key::Esc),
+    (&["Left"], Key::Left),
+    (&["Right"], Key::Right),
+    (&["Up"], Key::Up),
+    (&["Down"], Key::Down),
+    (&["Home"], Key::Home),
+    (&["End"], Key::End),
+    (&["PageUp"], Key::PageUp),
+    (&["PageDown"], Key::PageDown),
+    (&["S-Tab", "BackTab"], Key::BackTab),
+    (&["Del", "Delete"], Key::Delete),
+    (&["Insert", "Ins"], Key::Insert),
+    (&["Nul", "Null"], Key::Null),
+];
+
+fn named_key(name: &str) -> Option<Key> {
+    NAMED_KEYS
+        .iter()
+        .find(|(names, _)| names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+        .map(|(_, key)| *key)
+}
+
+/// The character a key name or single character stands for, for use after a
+/// modifier as in `<C-Space>`.
+fn key_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ if s.eq_ignore_ascii_case("gt") => Some('>'),
+        _ => match named_key(s) {
+            Some(Key::Char(c)) => Some(c),
+            _ => None,
+        },
+    }
+}
+
+/// Parses the inside of `<...>`, e.g. `C-a`, `Down` or `F5`.
+fn parse_key_name(name: &str) -> Option<Key> {
+    if let Some(key) = named_key(name) {
+        return Some(key);
+    }
+    let (modifier, rest) = match name.find('-') {
+        Some(i) if i > 0 && i + 1 < name.len() => (&name[..i], &name[i + 1..]),
+        _ => ("", name),
+    };
+    match modifier.to_ascii_uppercase().as_str() {
+        // Terminals cannot tell Ctrl-a from Ctrl-A
+        "C" => key_char(rest).map(|c| Key::Ctrl(c.to_ascii_lowercase())),
+        "M" | "A" => key_char(rest).map(Key::Alt),
+        "" => match rest.strip_prefix(|c| c == 'F' || c == 'f') {
+            Some(n) => match n.parse::<u8>() {
+                Ok(n) if (1..=12).contains(&n) => Some(Key::F(n)),
+                _ => None,
+            },
+            None => None,
+        },
+        _ => None,
+    }
+}
+
+/// Parses a single key, either in key notation or as a bare name like
+/// `Enter` or `C-x`.
+pub fn parse_key(s: &str) -> Option<Key> {
+    if let Some(inner) = s.strip_prefix('<').and_then(|x| x.strip_suffix('>')) {
+        return parse_key_name(inner);
+    }
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(Key::Char(c)),
+        (Some(_), Some(_)) => parse_key_name(s),
+        _ => None,
+    }
+}
+
+/// The name of a character inside `<...>` after a modifier.
+fn char_name(c: char) -> String {
+    match named_key_name(Key::Char(c)) {
+        Some(name) => String::from(name),
+        None if c == '>' => String::from("gt"),
+        None => c.to_string(),
+    }
+}
+
+fn named_key_name(key: Key) -> Option<&'static str> {
+    NAMED_KEYS
+        .iter()
+        .find(|(_, k)| *k == key)
+        .map(|(names, _)| names[0])
+}
+
+/// Writes `key` in key notation.
+pub fn format_key(key: Key) -> String {
+    if let Some(name) = named_key_name(key) {
+        return format!("<{}>", name);
+    }
+    match key {
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("<C-{}>", char_name(c)),
+        Key::Alt(c) => format!("<M-{}>", char_name(c)),
+        Key::F(n) => format!("<F{}>", n),
+        _ => String::from("<Nop>"),
+    }
+}
+
+/// The largest count a key sequence can carry, as in vim; larger ones are
+/// cut down to it so that repeating an action stays cheap.
+pub const MAX_COUNT: usize = 999_999_999;
+
+#[derive(Default, Clone, Debug)]
 pub struct KeySequence {
     keys: Vec<Key>,
 }
@@ -13,12 +139,58 @@ impl KeySequence {
             keys: Vec::from(keys),
         }
     }
-    pub fn default() -> Self {
-        Self { keys: Vec::new() }
+    /// Parses a key sequence in key notation, e.g. `gh` or `<C-w><Down>`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut keys: Vec<Key> = Vec::new();
+        let mut rest = s;
+        while let Some(c) = rest.chars().next() {
+            if c == '<' {
+                let end = rest.find('>')?;
+                keys.push(parse_key_name(&rest[1..end])?);
+                rest = &rest[end + 1..];
+            } else {
+                keys.push(Key::Char(c));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        if keys.is_empty() {
+            None
+        } else {
+            Some(Self { keys })
+        }
     }
     pub fn push(&mut self, key: Key) {
         self.keys.push(key);
     }
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+    /// Splits off a leading count as in `5j` or `10<C-d>`. A count cannot
+    /// start with `0`, so that key stays free for bindings, and is at most
+    /// `MAX_COUNT`.
+    pub fn split_count(&self) -> (Option<usize>, KeySequence) {
+        let digits = self
+            .keys
+            .iter()
+            .enumerate()
+            .take_while(|(i, k)| match k {
+                Key::Char('0') => *i > 0,
+                Key::Char(c) => c.is_ascii_digit(),
+                _ => false,
+            })
+            .count();
+        if digits == 0 {
+            return (None, self.clone());
+        }
+        let count = self.keys[..digits].iter().fold(0usize, |n, k| match k {
+            Key::Char(c) => n
+                .saturating_mul(10)
+                .saturating_add(c.to_digit(10).unwrap_or(0) as usize)
+                .min(MAX_COUNT),
+            _ => n,
+        });
+        (Some(count), Self::from_keys(&self.keys[digits..]))
+    }
     pub fn clear(&mut self) {
         self.keys.clear();
     }
@@ -27,6 +199,15 @@ impl KeySequence {
     }
 }
 
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for key in self.keys.iter() {
+            write!(f, "{}", format_key(*key))?;
+        }
+        Ok(())
+    }
+}
+
 impl Hash for KeySequence {
     fn hash<H: Hasher>(&self, state: &mut H) {
         for i in self.keys.iter().enumerate() {
@@ -41,3 +222,107 @@ impl PartialEq for KeySequence {
     }
 }
 impl Eq for KeySequence {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_named_keys() {
+        assert_eq!(parse_key("<CR>"), Some(Key::Char('\n')));
+        assert_eq!(parse_key("<enter>"), Some(Key::Char('\n')));
+        assert_eq!(parse_key("<Space>"), Some(Key::Char(' ')));
+        assert_eq!(parse_key("<lt>"), Some(Key::Char('<')));
+        assert_eq!(parse_key("<PageDown>"), Some(Key::PageDown));
+        assert_eq!(parse_key("<F12>"), Some(Key::F(12)));
+        assert_eq!(parse_key("<S-Tab>"), Some(Key::BackTab));
+        assert_eq!(parse_key("<C-A>"), Some(Key::Ctrl('a')));
+        assert_eq!(parse_key("<M-Space>"), Some(Key::Alt(' ')));
+        assert_eq!(parse_key("<C-->"), Some(Key::Ctrl('-')));
+        assert_eq!(parse_key("<F13>"), None);
+        assert_eq!(parse_key("<Foo>"), None);
+        assert_eq!(parse_key("<X-a>"), None);
+    }
+
+    #[test]
+    fn parse_bare_keys() {
+        assert_eq!(parse_key("x"), Some(Key::Char('x')));
+        assert_eq!(parse_key("C-x"), Some(Key::Ctrl('x')));
+        assert_eq!(parse_key("m-x"), Some(Key::Alt('x')));
+        assert_eq!(parse_key("Enter"), Some(Key::Char('\n')));
+        assert_eq!(parse_key("Down"), Some(Key::Down));
+        assert_eq!(parse_key(""), None);
+    }
+
+    #[test]
+    fn parse_sequences() {
+        let seq = KeySequence::parse("g<C-w><Down><lt>").unwrap();
+        let keys: Vec<Key> = seq.iter().copied().collect();
+        assert_eq!(
+            keys,
+            vec![Key::Char('g'), Key::Ctrl('w'), Key::Down, Key::Char('<')]
+        );
+        assert!(KeySequence::parse("a<Down").is_none());
+        assert!(KeySequence::parse("<Bogus>").is_none());
+        assert!(KeySequence::parse("").is_none());
+    }
+
+    #[test]
+    fn split_counts() {
+        let (count, rest) = KeySequence::parse("10<C-d>").unwrap().split_count();
+        assert_eq!(count, Some(10));
+        assert_eq!(rest.to_string(), "<C-d>");
+        let (count, rest) = KeySequence::parse("0j").unwrap().split_count();
+        assert_eq!(count, None);
+        assert_eq!(rest.to_string(), "0j");
+        let (count, rest) = KeySequence::parse("3").unwrap().split_count();
+        assert_eq!(count, Some(3));
+        assert!(rest.is_empty());
+        let (count, rest) = KeySequence::parse("99999999999999999999999j").unwrap().split_count();
+        assert_eq!(count, Some(MAX_COUNT));
+        assert_eq!(rest.to_string(), "j");
+    }
+
+    #[test]
+    fn format_round_trips() {
+        let mut keys = vec![
+            Key::Backspace,
+            Key::Left,
+            Key::Right,
+            Key::Up,
+            Key::Down,
+            Key::Home,
+            Key::End,
+            Key::PageUp,
+            Key::PageDown,
+            Key::BackTab,
+            Key::Delete,
+            Key::Insert,
+            Key::Null,
+            Key::Esc,
+            Key::Char('a'),
+            Key::Char('\n'),
+            Key::Char('\t'),
+            Key::Char(' '),
+            Key::Char('<'),
+            Key::Char('>'),
+            Key::Char('é'),
+            Key::Ctrl('a'),
+            Key::Ctrl(' '),
+            Key::Alt('A'),
+            Key::Alt('<'),
+            Key::Alt('>'),
+        ];
+        keys.extend((1..=12).map(Key::F));
+        for key in keys {
+            let s = format_key(key);
+            assert_eq!(parse_key(&s), Some(key), "{}", s);
+        }
+        let seq = KeySequence::from_keys(&[Key::Char('d'), Key::Ctrl('d'), Key::Char(' ')]);
+        assert_eq!(seq.to_string(), "d<C-d><Space>");
+        assert_eq!(KeySequence::parse(&seq.to_string()), Some(seq));
+        let seq = KeySequence::from_keys(&[Key::Alt('>'), Key::Char('>')]);
+        assert_eq!(seq.to_string(), "<M-gt>>");
+        assert_eq!(KeySequence::parse(&seq.to_string()), Some(seq));
+    }
+}
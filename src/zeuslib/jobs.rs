@@ -0,0 +1,314 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::zeuslib::events::{Event, EventSender};
+use crate::zeuslib::utils::fs::ops::{
+    copy_recursive_with, move_path_with, remove_recursive, total_size, unique_destination,
+};
+use crate::zeuslib::utils::fs::trash;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The file operation a job performs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobOp {
+    Copy,
+    Move,
+    Trash,
+    /// Moves entries of the trash back to where they came from.
+    Restore,
+    /// Removes entries for good.
+    Delete,
+}
+
+impl JobOp {
+    fn verb(self) -> &'static str {
+        match self {
+            JobOp::Copy => "Copy",
+            JobOp::Move => "Move",
+            JobOp::Trash => "Trash",
+            JobOp::Restore => "Restore",
+            JobOp::Delete => "Delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStatus {
+    Running,
+    Paused,
+    Cancelling,
+}
+
+/// A report sent from a worker thread back to the event loop.
+#[derive(Debug, Clone)]
+pub enum JobUpdate {
+    Progress {
+        done_bytes: u64,
+        total_bytes: u64,
+        current: PathBuf,
+    },
+    Finished(Result<(), String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct JobEvent {
+    pub id: usize,
+    pub update: JobUpdate,
+}
+
+/// Flags shared between a `Job` and its worker thread.
+#[derive(Clone, Default)]
+struct JobControl {
+    paused: Arc<AtomicBool>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobControl {
+    /// Blocks while the job is paused and fails once it has been cancelled.
+    fn checkpoint(&self) -> io::Result<()> {
+        loop {
+            if self.cancelled.load(Ordering::Relaxed) {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+            }
+            if !self.paused.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+    }
+}
+
+/// A running job as seen from the UI thread.
+pub struct Job {
+    pub id: usize,
+    pub op: JobOp,
+    pub sources: Vec<PathBuf>,
+    pub description: String,
+    pub done_bytes: u64,
+    pub total_bytes: u64,
+    pub current: Option<PathBuf>,
+    pub status: JobStatus,
+    control: JobControl,
+}
+
+impl Job {
+    pub fn ratio(&self) -> f64 {
+        if self.total_bytes == 0 {
+            0.0
+        } else {
+            (self.done_bytes as f64 / self.total_bytes as f64).min(1.0)
+        }
+    }
+}
+
+/// Owns the background jobs and the sender their workers report through.
+#[derive(Default)]
+pub struct JobManager {
+    pub jobs: Vec<Job>,
+    pub selected: usize,
+    next_id: usize,
+    sender: Option<EventSender>,
+}
+
+impl JobManager {
+    pub fn set_sender(&mut self, sender: EventSender) {
+        self.sender = Some(sender);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Starts `op` on `sources` on a worker thread. Copies and moves go into
+    /// `dest`; the other operations have none.
+    pub fn spawn(&mut self, op: JobOp, sources: Vec<PathBuf>, dest: Option<PathBuf>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut description = if sources.len() == 1 {
+            let name = sources[0].file_name().unwrap_or_default().to_string_lossy();
+            format!("{} {}", op.verb(), name)
+        } else {
+            format!("{} {} items", op.verb(), sources.len())
+        };
+        if let Some(dest) = &dest {
+            description.push_str(&format!(" to {}", dest.display()));
+        }
+        let control = JobControl::default();
+        self.jobs.push(Job {
+            id,
+            op,
+            sources: sources.clone(),
+            description,
+            done_bytes: 0,
+            total_bytes: 0,
+            current: None,
+            status: JobStatus::Running,
+            control: control.clone(),
+        });
+
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let report = |update: JobUpdate| {
+                if let Some(sender) = &sender {
+                    let _ = sender.send(Event::Job(JobEvent { id, update }));
+                }
+            };
+            let r = run_job(op, &sources, dest.as_deref(), &control, &report);
+            report(JobUpdate::Finished(r.map_err(|e| e.to_string())));
+        });
+        id
+    }
+
+    /// Applies a report from a worker. Returns the job once it has finished.
+    pub fn handle_event(&mut self, evt: JobEvent) -> Option<(Job, Result<(), String>)> {
+        let idx = self.jobs.iter().position(|j| j.id == evt.id)?;
+        match evt.update {
+            JobUpdate::Progress {
+                done_bytes,
+                total_bytes,
+                current,
+            } => {
+                let job = &mut self.jobs[idx];
+                job.done_bytes = done_bytes;
+                job.total_bytes = total_bytes;
+                job.current = Some(current);
+                None
+            }
+            JobUpdate::Finished(r) => {
+                let job = self.jobs.remove(idx);
+                if self.selected >= self.jobs.len() {
+                    self.selected = self.jobs.len().saturating_sub(1);
+                }
+                Some((job, r))
+            }
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.jobs.is_empty() {
+            self.selected = (self.selected + 1) % self.jobs.len();
+        }
+    }
+
+    pub fn selected_job(&self) -> Option<&Job> {
+        self.jobs.get(self.selected)
+    }
+
+    /// Pauses the selected job, or resumes it if it is already paused.
+    pub fn toggle_pause(&mut self) {
+        if let Some(job) = self.jobs.get_mut(self.selected) {
+            let paused = job.status == JobStatus::Paused;
+            match job.status {
+                JobStatus::Running => job.status = JobStatus::Paused,
+                JobStatus::Paused => job.status = JobStatus::Running,
+                JobStatus::Cancelling => return,
+            }
+            job.control.paused.store(!paused, Ordering::Relaxed);
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        if let Some(job) = self.jobs.get_mut(self.selected) {
+            job.status = JobStatus::Cancelling;
+            job.control.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+fn run_job(
+    op: JobOp,
+    sources: &[PathBuf],
+    dest: Option<&Path>,
+    control: &JobControl,
+    report: &dyn Fn(JobUpdate),
+) -> io::Result<()> {
+    let sizes: Vec<u64> = sources.iter().map(|p| total_size(p)).collect();
+    let total_bytes: u64 = sizes.iter().sum();
+    let mut done_bytes: u64 = 0;
+    let mut last_report = Instant::now();
+    for (src, size) in sources.iter().zip(sizes) {
+        let done_before = done_bytes;
+        control.checkpoint()?;
+        // Moving an entry into the directory it is in leaves it where it is
+        if op == JobOp::Move && src.parent() == dest {
+            done_bytes += size;
+            continue;
+        }
+        let dst = match dest {
+            Some(dest) => match unique_destination(src, dest) {
+                Some(dst) => Some(dst),
+                None => continue,
+            },
+            None => None,
+        };
+        report(JobUpdate::Progress {
+            done_bytes,
+            total_bytes,
+            current: src.clone(),
+        });
+        let mut progress = |n: u64| {
+            done_bytes += n;
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                last_report = Instant::now();
+                report(JobUpdate::Progress {
+                    done_bytes,
+                    total_bytes,
+                    current: src.clone(),
+                });
+            }
+            control.checkpoint()
+        };
+        match (op, dst) {
+            (JobOp::Copy, Some(dst)) => {
+                if let Err(e) = copy_recursive_with(src, &dst, &mut progress) {
+                    // Don't leave a half-copied tree behind
+                    let _ = remove_recursive(&dst);
+                    return Err(e);
+                }
+            }
+            (JobOp::Move, Some(dst)) => move_path_with(src, &dst, &mut progress)?,
+            (JobOp::Trash, _) => {
+                trash::trash_path(src, &mut progress)?;
+            }
+            (JobOp::Restore, _) => {
+                trash::restore(src, &mut progress)?;
+            }
+            (JobOp::Delete, _) => remove_recursive(src)?,
+            (JobOp::Copy, None) | (JobOp::Move, None) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "No destination"))
+            }
+        }
+        // A rename moves everything at once without reporting progress
+        done_bytes = done_bytes.max(done_before + size);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zeuslib::utils::testing::TempDir;
+
+    #[test]
+    fn moving_into_the_same_directory_does_nothing() {
+        let tmp = TempDir::new("jobs");
+        tmp.touch(&["a.txt", "sub/b.txt"]);
+        let dir = tmp.path();
+        let sources = vec![dir.join("a.txt"), dir.join("sub/b.txt")];
+        let control = JobControl::default();
+        run_job(JobOp::Move, &sources, Some(dir), &control, &|_| {}).unwrap();
+        assert!(dir.join("a.txt").is_file() && dir.join("b.txt").is_file());
+        assert!(!dir.join("a_1.txt").exists());
+        // Copies still get a name of their own
+        run_job(JobOp::Copy, &sources[..1], Some(dir), &control, &|_| {}).unwrap();
+        assert!(dir.join("a_1.txt").is_file());
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::zeuslib::events::{Event, EventSender};
+use crate::zeuslib::ui::filelist::FileListItem;
+
+/// How often a listing sends the entries it has read so far.
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A report sent from a thread reading a directory back to the event loop.
+#[derive(Debug, Clone)]
+pub enum ListUpdate {
+    Entries(Vec<FileListItem>),
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListEvent {
+    /// The file list that asked, see `FileList::id`.
+    pub list_id: usize,
+    /// Which of that list's reads this is from; reports of older ones are
+    /// dropped.
+    pub load_id: usize,
+    pub update: ListUpdate,
+}
+
+/// Reads `dir` on a worker thread, sending its entries with their metadata
+/// in batches. Stops early once `cancelled` is set.
+pub fn spawn_listing(
+    dir: PathBuf,
+    list_id: usize,
+    load_id: usize,
+    cancelled: Arc<AtomicBool>,
+    sender: EventSender,
+) {
+    thread::spawn(move || {
+        let report = |update: ListUpdate| {
+            let evt = ListEvent {
+                list_id,
+                load_id,
+                update,
+            };
+            sender.send(Event::List(evt)).is_ok()
+        };
+        let mut batch: Vec<FileListItem> = Vec::new();
+        let mut last_report = Instant::now();
+        if let Ok(entries) = dir.read_dir() {
+            for entry in entries.flatten() {
+                if cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                batch.push(FileListItem::new(entry.path()));
+                if last_report.elapsed() >= BATCH_INTERVAL {
+                    last_report = Instant::now();
+                    if !report(ListUpdate::Entries(std::mem::take(&mut batch))) {
+                        return;
+                    }
+                }
+            }
+        }
+        if !cancelled.load(Ordering::Relaxed) {
+            report(ListUpdate::Entries(batch));
+            report(ListUpdate::Finished);
+        }
+    });
+}
@@ -1,8 +1,23 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::time::Instant;
 
-pub use self::tabstate::{TabState, PANELS_PER_TAB};
+pub use self::options::Options;
+pub use self::register::{Register, RegisterMode};
+pub use self::tabstate::{TabState, MAIN_PANEL_IDX, PANELS_PER_TAB};
+use crate::zeuslib::config::Opener;
+use crate::zeuslib::events::EventSender;
+use crate::zeuslib::external::ExternalCommand;
 use crate::zeuslib::input::KeySequence;
+use crate::zeuslib::finder::Finder;
+use crate::zeuslib::jobs::JobManager;
+use crate::zeuslib::ui::cmdline::CommandLine;
+use crate::zeuslib::ui::dialog::Dialog;
+use crate::zeuslib::ui::finder::FinderView;
+use crate::zeuslib::ui::help::HelpView;
+use crate::zeuslib::utils::matcher::Matcher;
+use crate::zeuslib::watcher::Watcher;
 pub use crate::zeuslib::ui::filelist::{FileList, FileListItem, FileListRc};
 use crate::zeuslib::ui::panel::*;
 
@@ -15,13 +30,44 @@ pub struct State {
     pub tabs: Vec<TabState>,
     pub last_key_time: Option<Instant>,
     pub current_panel_idx: usize,
+    pub register: Register,
+    pub jobs: JobManager,
+    pub show_jobs: bool,
+    pub dialog: Option<Dialog>,
+    pub external_command: Option<ExternalCommand>,
+    pub openers: Vec<Opener>,
+    pub options: Options,
+    pub cmdline: CommandLine,
+    /// The help screen, while it is open.
+    pub help: Option<HelpView>,
+    /// The `/` prompt.
+    pub search_line: CommandLine,
+    /// The last search, repeated by `search_next` and `search_prev`.
+    pub search: Option<Matcher>,
+    /// The cursor position when the search being typed started.
+    pub search_origin: Option<usize>,
+    /// The prompt of the `filter` action.
+    pub filter_line: CommandLine,
+    /// The filter in place when the one being typed started, put back if it
+    /// is cancelled.
+    pub filter_origin: Option<Matcher>,
+    /// Walks directory trees for the finder.
+    pub finder: Finder,
+    /// The fuzzy finder, while it is open.
+    pub find: Option<FinderView>,
+    /// Where file lists report directory reads, given to those of new tabs.
+    pub list_sender: Option<EventSender>,
+    /// Watches the directories shown for changes made by other programs.
+    pub watcher: Watcher,
 }
 
-impl State {
-    pub fn default() -> Self {
+impl Default for State {
+    fn default() -> Self {
         Self::from_tab_count(1)
     }
+}
 
+impl State {
     pub fn from_tab_count(tab_count: u8) -> Self {
         let tabs: Vec<TabState> = (0..(tab_count)).map(|_x| TabState::default()).collect();
         let mut state = Self {
@@ -31,6 +77,24 @@ impl State {
             tabs,
             last_key_time: None,
             current_panel_idx: DEFAULT_PANEL_IDX,
+            register: Register::default(),
+            jobs: JobManager::default(),
+            show_jobs: false,
+            dialog: None,
+            external_command: None,
+            openers: Vec::new(),
+            options: Options::default(),
+            cmdline: CommandLine::default(),
+            help: None,
+            search_line: CommandLine::new("/"),
+            search: None,
+            search_origin: None,
+            filter_line: CommandLine::new("filter: "),
+            filter_origin: None,
+            finder: Finder::default(),
+            find: None,
+            list_sender: None,
+            watcher: Watcher::default(),
         };
         state.refresh();
         state.select_initial_panel();
@@ -69,6 +133,7 @@ impl State {
             tab.update_preview();
         }
     }
+    #[allow(clippy::result_unit_err)]
     pub fn get_current_panel(&self) -> Result<FileListRc, ()> {
         let idx: usize = self.current_panel_idx;
         if self.current_panel_idx < PANELS_PER_TAB {
@@ -81,6 +146,7 @@ impl State {
         Err(())
     }
 
+    #[allow(clippy::result_unit_err)]
     pub fn get_current_panel_mut(&mut self) -> Result<FileListRc, ()> {
         let idx: usize = self.current_panel_idx;
         if self.current_panel_idx < PANELS_PER_TAB {
@@ -118,9 +184,173 @@ impl State {
         None
     }
 
+    /// Paths an action should operate on: the marked items of the current
+    /// panel, or the item under the cursor when nothing is marked.
+    pub fn target_paths(&self) -> Vec<PathBuf> {
+        if let Ok(Some(panel)) = self.get_current_panel() {
+            let panel = panel.borrow();
+            let marked: Vec<PathBuf> = panel
+                .items
+                .iter()
+                .filter(|x| x.marked)
+                .map(|x| x.path.clone())
+                .collect();
+            if !marked.is_empty() {
+                return marked;
+            }
+            if let Some(item) = panel.selected_item() {
+                return vec![item.path];
+            }
+        }
+        Vec::new()
+    }
+
+    /// The selected entry and up to `n - 1` entries after it.
+    pub fn paths_from_selected(&self, n: usize) -> Vec<PathBuf> {
+        if let Ok(Some(panel)) = self.get_current_panel() {
+            let panel = panel.borrow();
+            if let Some(i) = panel.selected() {
+                return panel
+                    .items
+                    .iter()
+                    .skip(i)
+                    .take(n)
+                    .map(|x| x.path.clone())
+                    .collect();
+            }
+        }
+        Vec::new()
+    }
+
+    /// Highlights the matches of `matcher` in every file list.
+    pub fn set_highlight(&mut self, matcher: Option<&Matcher>) {
+        for tab in self.tabs.iter() {
+            for panel in tab.panels.iter() {
+                if let Panel::FileListPanel(Some(panel)) = panel {
+                    panel.borrow_mut().highlight = matcher.cloned();
+                }
+            }
+        }
+    }
+
+    /// Moves the cursor to the nearest entry that matches, searching down or
+    /// up from `from` as `FileList::find_match` does. Returns whether one was
+    /// found.
+    pub fn jump_to_match(
+        &mut self,
+        matcher: &Matcher,
+        from: usize,
+        forward: bool,
+        inclusive: bool,
+    ) -> bool {
+        let found = match self.get_current_panel_mut() {
+            Ok(Some(panel)) => {
+                let mut panel = panel.borrow_mut();
+                let found = panel.find_match(matcher, from, forward, inclusive);
+                if let Some(i) = found {
+                    panel.select(i);
+                }
+                found.is_some()
+            }
+            _ => false,
+        };
+        if found {
+            self.get_current_tab_mut().update_preview();
+        }
+        found
+    }
+
+    pub fn clear_marks(&mut self) {
+        if let Ok(Some(panel)) = self.get_current_panel_mut() {
+            let mut panel = panel.borrow_mut();
+            for item in panel.items.iter_mut() {
+                item.marked = false;
+            }
+        }
+    }
+
+    /// Hands the current options to every file list and lists their entries
+    /// again, keeping the cursor on the same item.
+    pub fn apply_options(&mut self) {
+        let list = self.options.list;
+        for tab in self.tabs.iter_mut() {
+            let mut main_changed = false;
+            for (i, panel) in tab.panels.iter().enumerate() {
+                if let Panel::FileListPanel(Some(panel)) = panel {
+                    let mut panel = panel.borrow_mut();
+                    let before = panel.selected_item().map(|x| x.path);
+                    panel.options = list;
+                    panel.relist();
+                    let moved = panel.selected_item().map(|x| x.path) != before;
+                    main_changed |= i == MAIN_PANEL_IDX && moved;
+                }
+            }
+            if main_changed {
+                tab.update_preview();
+            }
+        }
+    }
+
+    /// Has every file list, including those of tabs opened later, read
+    /// directories in the background and report to `sender`.
+    pub fn set_list_sender(&mut self, sender: EventSender) {
+        for tab in self.tabs.iter_mut() {
+            tab.set_sender(&sender);
+        }
+        self.list_sender = Some(sender);
+    }
+
+    /// Watches the directories listed in any tab, and only those.
+    pub fn watch_dirs(&mut self) {
+        let mut dirs: HashSet<PathBuf> = HashSet::new();
+        for tab in self.tabs.iter() {
+            for panel in tab.panels.iter() {
+                if let Panel::FileListPanel(Some(panel)) = panel {
+                    dirs.insert(panel.borrow().root().to_path_buf());
+                }
+            }
+        }
+        self.watcher.watch_dirs(&dirs);
+    }
+
+    /// Reads the file lists showing any of `dirs` again, in every tab. The
+    /// cursor and marks stay on the entries they were on. A tab whose
+    /// directory is gone goes to the nearest one still there.
+    pub fn refresh_dirs(&mut self, dirs: &[PathBuf]) {
+        for tab in self.tabs.iter_mut() {
+            let gone = match &tab.dir {
+                Some(dir) => dirs.iter().any(|x| dir.starts_with(x)) && !dir.is_dir(),
+                None => false,
+            };
+            if gone {
+                let dir = tab.dir.clone().unwrap_or_default();
+                tab.cd(dir.ancestors().find(|x| x.is_dir()).map(Path::to_path_buf));
+                tab.update_preview();
+                continue;
+            }
+            let mut main_changed = false;
+            for (i, panel) in tab.panels.iter().enumerate() {
+                if let Panel::FileListPanel(Some(panel)) = panel {
+                    let mut panel = panel.borrow_mut();
+                    if dirs.iter().any(|x| x == panel.root()) {
+                        panel.refresh_list();
+                        main_changed |= i == MAIN_PANEL_IDX;
+                    }
+                }
+            }
+            if main_changed {
+                tab.update_preview();
+            }
+        }
+    }
+
     pub fn new_tab(&mut self) {
-        self.tabs.push(TabState::default());
-        self.refresh();
+        let mut tab = TabState::default();
+        if let Some(sender) = &self.list_sender {
+            tab.set_sender(sender);
+        }
+        self.tabs.push(tab);
+        self.apply_options();
     }
 
     pub fn next_tab(&mut self) {
@@ -141,3 +371,5 @@ impl State {
 }
 
 pub mod tabstate;
+pub mod register;
+pub mod options;
@@ -0,0 +1,238 @@
+use crate::zeuslib::ui::filelist::{ListOptions, SortKey};
+use crate::zeuslib::utils::fs::walk::WalkOptions;
+use crate::zeuslib::utils::glob::Glob;
+use crate::zeuslib::utils::matcher::{ignores_case, MatchKind, Matcher};
+
+/// Settings that can be changed at runtime with `:set`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub list: ListOptions,
+    /// How long to wait, in milliseconds, for the rest of a key sequence
+    /// when the keys so far are bound but also start a longer binding.
+    pub key_timeout: u64,
+    pub ignore_case: bool,
+    /// Match case anyway if the pattern has an upper-case letter.
+    pub smart_case: bool,
+    /// Whether search patterns are regular expressions instead of plain text.
+    pub search_regex: bool,
+    /// Globs of names the finder neither lists nor enters.
+    pub find_ignore: Vec<String>,
+    /// Whether the finder also leaves out what `.gitignore` files list.
+    pub git_ignore: bool,
+    /// How long, in milliseconds, a watched directory must go without
+    /// changes before its listing is refreshed.
+    pub watch_delay: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            list: ListOptions::default(),
+            key_timeout: 1000,
+            ignore_case: true,
+            smart_case: true,
+            search_regex: false,
+            find_ignore: vec![String::from(".git")],
+            git_ignore: true,
+            watch_delay: 200,
+        }
+    }
+}
+
+/// Names accepted by `Options::set`, for completion.
+pub const OPTION_NAMES: &[&str] = &[
+    "findignore",
+    "gitignore",
+    "hidden",
+    "ignorecase",
+    "scrolloff",
+    "searchregex",
+    "smartcase",
+    "sort",
+    "sortreverse",
+    "timeoutlen",
+    "watchdelay",
+    "wrapscroll",
+];
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "on" | "yes" | "1" => Ok(true),
+        "false" | "off" | "no" | "0" => Ok(false),
+        _ => Err(format!("Invalid value for {}: '{}'", name, value)),
+    }
+}
+
+impl Options {
+    /// Applies one vim-style assignment: `name`, `noname` and `name!` set,
+    /// clear and toggle a boolean option, `name=value` sets any option.
+    pub fn set(&mut self, expr: &str) -> Result<(), String> {
+        let (name, value) = match expr.find('=') {
+            Some(i) => (&expr[..i], Some(&expr[i + 1..])),
+            None => (expr, None),
+        };
+        if let Some(name) = name.strip_suffix('!') {
+            let current = self.get_bool(name)?;
+            return self.set_bool(name, !current);
+        }
+        if let (Some(name), None) = (name.strip_prefix("no"), value) {
+            if self.get_bool(name).is_ok() {
+                return self.set_bool(name, false);
+            }
+        }
+        match name {
+            "sort" => {
+                let value = value.unwrap_or_default();
+                self.list.sort = SortKey::parse(value)
+                    .ok_or_else(|| format!("Unknown sort key: '{}'", value))?;
+                Ok(())
+            }
+            "scrolloff" => {
+                let value = value.unwrap_or_default();
+                self.list.scrolloff = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for scrolloff: '{}'", value))?;
+                Ok(())
+            }
+            "timeoutlen" => {
+                let value = value.unwrap_or_default();
+                self.key_timeout = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for timeoutlen: '{}'", value))?;
+                Ok(())
+            }
+            "watchdelay" => {
+                let value = value.unwrap_or_default();
+                self.watch_delay = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for watchdelay: '{}'", value))?;
+                Ok(())
+            }
+            "findignore" => {
+                let globs: Vec<String> = value
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|x| String::from(x.trim()))
+                    .filter(|x| !x.is_empty())
+                    .collect();
+                for glob in globs.iter() {
+                    Glob::new(glob).map_err(|e| format!("Invalid glob '{}': {}", glob, e))?;
+                }
+                self.find_ignore = globs;
+                Ok(())
+            }
+            _ => {
+                let value = match value {
+                    Some(v) => parse_bool(name, v)?,
+                    None => true,
+                };
+                self.set_bool(name, value)
+            }
+        }
+    }
+
+    fn get_bool(&self, name: &str) -> Result<bool, String> {
+        match name {
+            "hidden" => Ok(self.list.show_hidden),
+            "sortreverse" => Ok(self.list.sort_reverse),
+            "wrapscroll" => Ok(self.list.wrap_scroll),
+            "ignorecase" => Ok(self.ignore_case),
+            "smartcase" => Ok(self.smart_case),
+            "searchregex" => Ok(self.search_regex),
+            "gitignore" => Ok(self.git_ignore),
+            _ => Err(format!("Unknown option: {}", name)),
+        }
+    }
+
+    fn set_bool(&mut self, name: &str, value: bool) -> Result<(), String> {
+        match name {
+            "hidden" => self.list.show_hidden = value,
+            "sortreverse" => self.list.sort_reverse = value,
+            "wrapscroll" => self.list.wrap_scroll = value,
+            "ignorecase" => self.ignore_case = value,
+            "smartcase" => self.smart_case = value,
+            "searchregex" => self.search_regex = value,
+            "gitignore" => self.git_ignore = value,
+            _ => return Err(format!("Unknown option: {}", name)),
+        }
+        Ok(())
+    }
+
+    /// A matcher for a search `pattern` following the search options.
+    pub fn search_matcher(&self, pattern: &str) -> Result<Matcher, String> {
+        let kind = if self.search_regex { MatchKind::Regex } else { MatchKind::Substring };
+        let ignore_case = ignores_case(pattern, self.ignore_case, self.smart_case);
+        Matcher::new(pattern, kind, ignore_case)
+    }
+
+    /// A matcher for a filter `pattern`, whose kind is given by the pattern
+    /// as in `MatchKind::detect`.
+    pub fn filter_matcher(&self, pattern: &str) -> Result<Matcher, String> {
+        let (kind, body) = MatchKind::detect(pattern);
+        let ignore_case = ignores_case(body, self.ignore_case, self.smart_case);
+        let mut matcher = Matcher::new(body, kind, ignore_case)?;
+        matcher.set_label(pattern);
+        Ok(matcher)
+    }
+
+    /// What the finder leaves out of a walk.
+    pub fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            ignore: self.find_ignore.iter().filter_map(|x| Glob::new(x).ok()).collect(),
+            git_ignore: self.git_ignore,
+        }
+    }
+
+    /// All current values in `:set` syntax.
+    pub fn describe(&self) -> String {
+        let no = |on: bool| if on { "" } else { "no" };
+        format!(
+            "{}hidden sort={} {}sortreverse scrolloff={} {}wrapscroll timeoutlen={} \
+             {}ignorecase {}smartcase {}searchregex findignore={} {}gitignore watchdelay={}",
+            no(self.list.show_hidden),
+            self.list.sort.as_str(),
+            no(self.list.sort_reverse),
+            self.list.scrolloff,
+            no(self.list.wrap_scroll),
+            self.key_timeout,
+            no(self.ignore_case),
+            no(self.smart_case),
+            no(self.search_regex),
+            self.find_ignore.join(","),
+            no(self.git_ignore),
+            self.watch_delay,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_options() {
+        let mut options = Options::default();
+        options.set("nohidden").unwrap();
+        assert!(!options.list.show_hidden);
+        options.set("hidden!").unwrap();
+        assert!(options.list.show_hidden);
+        options.set("hidden=off").unwrap();
+        assert!(!options.list.show_hidden);
+        options.set("sort=mtime").unwrap();
+        assert_eq!(options.list.sort, SortKey::Mtime);
+        assert!(options.set("sort=color").is_err());
+        options.set("nowrapscroll").unwrap();
+        assert!(!options.list.wrap_scroll);
+        options.set("scrolloff=5").unwrap();
+        assert_eq!(options.list.scrolloff, 5);
+        options.set("timeoutlen=300").unwrap();
+        assert_eq!(options.key_timeout, 300);
+        assert!(options.set("timeoutlen=soon").is_err());
+        options.set("watchdelay=500").unwrap();
+        assert_eq!(options.watch_delay, 500);
+        assert!(options.set("bogus").is_err());
+        options.set("findignore=.git, target").unwrap();
+        assert_eq!(options.find_ignore, vec![".git", "target"]);
+        assert_eq!(options.walk_options().ignore.len(), 2);
+    }
+}
@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// What should happen to the paths in a `Register` when they are pasted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterMode {
+    Copy,
+    Move,
+}
+
+/// Paths that were yanked or cut, waiting to be pasted.
+///
+/// The register lives on `State` rather than on a tab, so items yanked in one
+/// tab can be pasted in another.
+#[derive(Debug, Clone)]
+pub struct Register {
+    pub paths: Vec<PathBuf>,
+    pub mode: RegisterMode,
+}
+
+impl Default for Register {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            mode: RegisterMode::Copy,
+        }
+    }
+}
+
+impl Register {
+    pub fn set(&mut self, paths: Vec<PathBuf>, mode: RegisterMode) {
+        self.paths = paths;
+        self.mode = mode;
+    }
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.mode = RegisterMode::Copy;
+    }
+    /// Forgets cut paths once a move of `sources` has finished: all of them
+    /// if it succeeded, otherwise those that were moved before it failed.
+    /// A register that was filled again in the meantime is left alone.
+    pub fn finish_move(&mut self, sources: &[PathBuf], succeeded: bool) {
+        if self.mode != RegisterMode::Move || self.paths != sources {
+            return;
+        }
+        if succeeded {
+            self.clear();
+        } else {
+            self.paths.retain(|p| fs::symlink_metadata(p).is_ok());
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
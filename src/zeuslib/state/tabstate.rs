@@ -1,16 +1,21 @@
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
+use crate::zeuslib::events::EventSender;
 use crate::zeuslib::ui::filelist::{FileList, Rc, RefCell};
 use crate::zeuslib::ui::panel::*;
+use crate::zeuslib::utils::matcher::Matcher;
 
 pub const PANELS_PER_TAB: usize = 3;
-const MAIN_PANEL_IDX: usize = 1;
+/// Index of the panel listing the current directory.
+pub const MAIN_PANEL_IDX: usize = 1;
 
 pub struct TabState {
     pub dir: Option<PathBuf>,
         pub panels: Vec<Panel>,
     pub marked_paths: HashSet<PathBuf>,
+    /// Filters set in this tab, by the directory they apply to.
+    pub filters: HashMap<PathBuf, Matcher>,
 }
 
 impl Default for TabState {
@@ -18,11 +23,11 @@ impl Default for TabState {
         let dir = std::env::current_dir().expect("Failed to find current directory");
         let panels: [Panel; PANELS_PER_TAB] = {
             let left = if let Some(p) = dir.parent() {
-                Panel::FileListPanel(Some(Rc::new(RefCell::new(FileList::new(p.to_str().unwrap())))))
+                Panel::FileListPanel(Some(Rc::new(RefCell::new(FileList::new(p)))))
             } else {
                 Panel::EmptyPanel
             };
-            let center = Some(Rc::new(RefCell::new(FileList::new(dir.to_str().unwrap()))));
+            let center = Some(Rc::new(RefCell::new(FileList::new(&dir))));
             let right = Rc::new(RefCell::new(Preview::default()));
             [left, Panel::FileListPanel(center), Panel::PreviewPanel(right)]
         };
@@ -30,11 +35,32 @@ impl Default for TabState {
             dir: Some(dir),
             panels: Vec::from(panels),
             marked_paths: HashSet::new(),
+            filters: HashMap::new(),
         }
     }
 }
 
 impl TabState {
+    /// Has the file lists of the tab read directories in the background,
+    /// see `FileList::set_sender`.
+    pub fn set_sender(&mut self, sender: &EventSender) {
+        for panel in self.panels.iter() {
+            if let Panel::FileListPanel(Some(panel)) = panel {
+                panel.borrow_mut().set_sender(sender.clone());
+            }
+        }
+    }
+
+    /// The file list with the given id and whether it is the main panel.
+    pub fn find_list(&self, id: usize) -> Option<(Rc<RefCell<FileList>>, bool)> {
+        self.panels.iter().enumerate().find_map(|(i, panel)| match panel {
+            Panel::FileListPanel(Some(panel)) if panel.borrow().id() == id => {
+                Some((Rc::clone(panel), i == MAIN_PANEL_IDX))
+            }
+            _ => None,
+        })
+    }
+
     pub fn cd(&mut self, new_dir: Option<PathBuf>) {
         self.dir = new_dir.clone();
         if let Some(new_dir) = new_dir {
@@ -44,8 +70,7 @@ impl TabState {
                     let mut panel = left.borrow_mut();
                     let parent = new_dir.parent();
                     if let Some(parent) = parent {
-                        panel.set_root(parent.to_str().expect("Invalid path!"));
-                        panel.refresh_list();
+                        panel.set_root(parent);
                     } else {
                         left_empty = true;
                     }
@@ -61,8 +86,8 @@ impl TabState {
                 if let Panel::FileListPanel(Some(center)) = &self.panels[MAIN_PANEL_IDX] {
                     let mut panel = center.borrow_mut();
                     let pos = panel.cursor_pos;
-                    panel.set_root(new_dir.to_str().expect("Invalid path!"));
-                    panel.refresh_list();
+                    panel.filter = self.filters.get(&new_dir).cloned();
+                    panel.set_root(&new_dir);
                     panel.select(pos);
                 }
             }
@@ -72,11 +97,7 @@ impl TabState {
     pub fn cd_parent(&mut self) {
         let new_dir: Option<PathBuf> = if let Some(dir) = &self.dir {
             let parent = dir.parent();
-            if let Some(parent) = parent {
-                Some(parent.to_path_buf())
-            } else {
-                None
-            }
+            parent.map(|parent| parent.to_path_buf())
         } else {
             None
         };
@@ -93,7 +114,7 @@ impl TabState {
                 if !item.is_dir() {
                     return
                 }
-                path = Some(PathBuf::from(item.path));
+                path = Some(item.path);
             }
         }
         {
@@ -103,6 +124,36 @@ impl TabState {
         }
     }
 
+    /// The filter of the current directory.
+    pub fn filter(&self) -> Option<&Matcher> {
+        self.dir.as_ref().and_then(|dir| self.filters.get(dir))
+    }
+
+    /// Sets or clears the filter of the current directory and lists it
+    /// again, keeping the cursor on the same entry if it is still shown.
+    pub fn set_filter(&mut self, filter: Option<Matcher>) {
+        if let Some(dir) = &self.dir {
+            match &filter {
+                Some(filter) => self.filters.insert(dir.clone(), filter.clone()),
+                None => self.filters.remove(dir),
+            };
+        }
+        if let Panel::FileListPanel(Some(panel)) = &self.panels[MAIN_PANEL_IDX] {
+            let mut panel = panel.borrow_mut();
+            panel.filter = filter;
+            panel.relist();
+        }
+        self.update_preview();
+    }
+
+    /// Puts the cursor of the main panel on `path` if it is listed there.
+    pub fn select_path(&mut self, path: &Path) {
+        if let Panel::FileListPanel(Some(panel)) = &self.panels[MAIN_PANEL_IDX] {
+            panel.borrow_mut().select_path(path);
+        }
+        self.update_preview();
+    }
+
     pub fn update_preview(&mut self) {
         if let Panel::PreviewPanel(preview) = &self.panels[2] {
             let mut preview = preview.borrow_mut();
@@ -110,8 +161,7 @@ impl TabState {
                 let main_panel = main_panel.borrow();
                 let selected_item = main_panel.selected_item();
                 if let Some(selected_item) = selected_item {
-                    let p = selected_item.path;
-                    preview.set_path(Some(PathBuf::from(p)));
+                    preview.set_path(Some(selected_item.path));
                 }
             }
         }
@@ -2,6 +2,12 @@ pub mod filelist;
 pub mod preview;
 pub mod drawable;
 pub mod panel;
+pub mod jobs;
+pub mod dialog;
+pub mod cmdline;
+pub mod whichkey;
+pub mod help;
+pub mod finder;
 
 extern crate termion;
 use std::io::{self};
@@ -10,16 +16,21 @@ use tui::layout::{Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Style};
 use tui::symbols::DOT;
 use tui::text::Spans;
-use tui::widgets::{Block, Borders, Tabs};
+use tui::widgets::{Block, Borders, Paragraph, Tabs};
 use tui::{Frame, Terminal};
 
+use crate::zeuslib::config::Config;
 use crate::zeuslib::state::State;
 pub use crate::zeuslib::ui::drawable::Drawable;
+use crate::zeuslib::ui::jobs::JobsView;
+use crate::zeuslib::ui::whichkey::WhichKeyView;
+use crate::zeuslib::utils::fs::info::{recent_fs_space, FileMeta};
+use crate::zeuslib::utils::fs::FileSize;
 pub use crate::zeuslib::Backend;
 
-#[allow(dead_code)]
 struct LayoutRects {
     panels: Vec<Rect>,
+    center: Rect,
     header: Rect,
     footer: Rect,
 }
@@ -34,7 +45,7 @@ impl LayoutRects {
                 [
                     Constraint::Length(3),
                     Constraint::Min(1),
-                    Constraint::Length(3),
+                    Constraint::Length(4),
                 ]
                 .as_ref(),
             )
@@ -47,6 +58,7 @@ impl LayoutRects {
 
         Self {
             panels: vec![center[0], center[1], center[2]],
+            center: top_level[1],
             header: top_level[0],
             footer: top_level[2],
         }
@@ -56,7 +68,7 @@ impl LayoutRects {
 fn make_tab_names(state: &State) -> Vec<String> {
     let tc: u32 = state.tabs.len() as u32;
     let nums = 1u32..(tc + 1u32);
-    let nums_vec: Vec<_> = nums.map(u32::from).collect();
+    let nums_vec: Vec<_> = nums.collect();
 
     nums_vec.iter().map(|x| format!("Tab {}", x)).collect()
 }
@@ -68,7 +80,7 @@ fn draw_tabs(f: &mut Frame<Backend>, state: &State, layout: &LayoutRects) {
         .map(Spans::from)
         .collect();
     let tabs = Tabs::new(titles)
-        .select(state.current_tab.into())
+        .select(state.current_tab)
         .block(Block::default().borders(Borders::ALL))
         .style(Style::default().fg(Color::White))
         .highlight_style(Style::default().fg(Color::Yellow))
@@ -87,13 +99,187 @@ fn draw_panels(f: &mut Frame<Backend>, state: &mut State, layout: &LayoutRects)
     }
 }
 
+fn draw_jobs(f: &mut Frame<Backend>, state: &State, layout: &LayoutRects) {
+    if !state.show_jobs {
+        return;
+    }
+    let mut view = JobsView::new(&state.jobs);
+    let area = layout.center;
+    let height = view.height().min(area.height);
+    let rect = Rect::new(area.x, area.y + area.height - height, area.width, height);
+    view.draw(f, &rect);
+}
+
+/// Shows what can follow the keys typed so far, over the bottom of the panels.
+fn draw_which_key(f: &mut Frame<Backend>, state: &State, config: &Config, layout: &LayoutRects) {
+    let (_, keys) = state.key_seq.split_count();
+    if keys.is_empty() {
+        return;
+    }
+    let mut view = WhichKeyView::new(config, &keys);
+    if view.is_empty() {
+        return;
+    }
+    let area = layout.center;
+    let height = view.height(area.width).min(area.height);
+    let rect = Rect::new(area.x, area.y + area.height - height, area.width, height);
+    view.draw(f, &rect);
+}
+
+fn jobs_summary(state: &State) -> String {
+    let jobs = &state.jobs.jobs;
+    if jobs.is_empty() {
+        return String::new();
+    }
+    let done: u64 = jobs.iter().map(|j| j.done_bytes).sum();
+    let total: u64 = jobs.iter().map(|j| j.total_bytes).sum();
+    let percent = (done * 100).checked_div(total).unwrap_or(0);
+    format!("{} job(s) {}%", jobs.len(), percent)
+}
+
+/// Permissions, owner, size and mtime of the entry under the cursor.
+fn selection_summary(state: &State) -> String {
+    match state.get_current_panel() {
+        Ok(Some(panel)) => panel
+            .borrow()
+            .selected_item()
+            .map(|x| x.meta.describe())
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// How many entries of the current panel are marked and their size;
+/// directories count as their own entry, not their contents.
+fn marked_summary(state: &State) -> String {
+    let panel = match state.get_current_panel() {
+        Ok(Some(panel)) => panel,
+        _ => return String::new(),
+    };
+    let panel = panel.borrow();
+    let marked: Vec<&FileMeta> = panel.items.iter().filter(|x| x.marked).map(|x| &x.meta).collect();
+    if marked.is_empty() {
+        return String::new();
+    }
+    let size: u64 = marked.iter().filter(|m| m.is_file).map(|m| m.size).sum();
+    format!("{} marked, {}", marked.len(), FileSize::from_total_bytes(size))
+}
+
+/// Free and total space on the filesystem of the current directory, as of
+/// the last few seconds.
+fn space_summary(state: &State) -> String {
+    let space = state.get_current_tab().dir.as_deref().and_then(recent_fs_space);
+    match space {
+        Some(space) => format!(
+            "{} free of {}",
+            FileSize::from_total_bytes(space.free),
+            FileSize::from_total_bytes(space.total)
+        ),
+        None => String::new(),
+    }
+}
+
+/// The second footer line: the selected entry on the left, marked entries
+/// and disk space on the right.
+fn draw_status(f: &mut Frame<Backend>, state: &State, area: Rect) {
+    let marked = marked_summary(state);
+    let space = space_summary(state);
+    let right = match (marked.is_empty(), space.is_empty()) {
+        (false, false) => format!("{}  |  {}", marked, space),
+        _ => format!("{}{}", marked, space),
+    };
+    let parts = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [Constraint::Min(1), Constraint::Length(right.chars().count() as u16)].as_ref(),
+        )
+        .split(area);
+    let dim = Style::default().fg(Color::Gray);
+    let selection = selection_summary(state);
+    f.render_widget(Paragraph::new(selection.as_str()).style(dim), parts[0]);
+    f.render_widget(Paragraph::new(right.as_str()).style(dim), parts[1]);
+}
+
+fn draw_footer(f: &mut Frame<Backend>, state: &mut State, layout: &LayoutRects) {
+    let block = Block::default().borders(Borders::ALL);
+    let area = block.inner(layout.footer);
+    f.render_widget(block, layout.footer);
+    if area.height == 0 {
+        return;
+    }
+    let inner = Rect::new(area.x, area.y, area.width, 1);
+    if area.height > 1 {
+        let status = Rect::new(area.x, area.y + 1, area.width, 1);
+        draw_status(f, state, status);
+    }
+
+    let summary = jobs_summary(state);
+    // Count and keys typed so far of a sequence that is not complete yet
+    let pending = state.key_seq.to_string();
+    let pending_width = if pending.is_empty() { 0 } else { pending.chars().count() + 1 };
+    let parts = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Min(1),
+                Constraint::Length(pending_width as u16),
+                Constraint::Length(summary.len() as u16),
+            ]
+            .as_ref(),
+        )
+        .split(inner);
+    if state.cmdline.is_active() {
+        state.cmdline.draw(f, &parts[0]);
+        state.cmdline.draw_completions(f, &layout.footer);
+    } else if state.search_line.is_active() {
+        state.search_line.draw(f, &parts[0]);
+    } else if state.filter_line.is_active() {
+        state.filter_line.draw(f, &parts[0]);
+    } else {
+        f.render_widget(Paragraph::new(state.message.as_str()), parts[0]);
+    }
+    f.render_widget(Paragraph::new(pending.as_str()), parts[1]);
+    f.render_widget(
+        Paragraph::new(summary.as_str()).style(Style::default().fg(Color::Yellow)),
+        parts[2],
+    );
+}
+
+fn draw_help(f: &mut Frame<Backend>, state: &mut State, config: &Config) {
+    if let Some(help) = &mut state.help {
+        let size = f.size();
+        help.draw(f, size, config);
+    }
+}
+
+/// The finder covers the panels, leaving the tabs and the footer visible.
+fn draw_finder(f: &mut Frame<Backend>, state: &mut State, layout: &LayoutRects) {
+    if let Some(finder) = &mut state.find {
+        finder.draw(f, layout.center);
+    }
+}
+
+fn draw_dialog(f: &mut Frame<Backend>, state: &mut State) {
+    if let Some(dialog) = &mut state.dialog {
+        let size = f.size();
+        dialog.draw(f, &size);
+    }
+}
+
 pub fn draw(
     terminal: &mut Terminal<Backend>,
-    mut state: &mut State,
+    state: &mut State,
+    config: &Config,
 ) -> Result<(), io::Error> {
     terminal.draw(|f| {
         let layout = LayoutRects::new(f);
-        draw_tabs(f, &state, &layout);
-        draw_panels(f, &mut state, &layout);
+        draw_tabs(f, state, &layout);
+        draw_panels(f, state, &layout);
+        draw_jobs(f, state, &layout);
+        draw_which_key(f, state, config, &layout);
+        draw_footer(f, state, &layout);
+        draw_finder(f, state, &layout);
+        draw_help(f, state, config);
+        draw_dialog(f, state);
     })
 }
@@ -0,0 +1,258 @@
+use termion::event::Key;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Clear, Paragraph};
+use tui::Frame;
+
+use crate::zeuslib::ui::dialog::LineInput;
+use crate::zeuslib::ui::Drawable;
+use crate::zeuslib::Backend;
+
+const MAX_HISTORY: usize = 100;
+
+/// What handling a key did to the command line.
+pub enum CommandLineOutcome {
+    /// The command line stays open.
+    Pending,
+    /// Enter was pressed; the line should be run.
+    Submit(String),
+    /// The command line was closed without running anything.
+    Cancel,
+}
+
+/// A prompt at the bottom of the screen, such as `:` for commands or `/` for
+/// searches, with history and completion.
+pub struct CommandLine {
+    prompt: &'static str,
+    input: Option<LineInput>,
+    history: Vec<String>,
+    /// Index into `history` while browsing it with Up and Down.
+    history_pos: Option<usize>,
+    /// The line as typed before browsing history started.
+    stash: String,
+    /// Completed lines offered by the last Tab, cycled by further Tabs.
+    completions: Vec<String>,
+    completion_idx: usize,
+}
+
+impl Default for CommandLine {
+    fn default() -> Self {
+        Self::new(":")
+    }
+}
+
+impl CommandLine {
+    pub fn new(prompt: &'static str) -> Self {
+        Self {
+            prompt,
+            input: None,
+            history: Vec::new(),
+            history_pos: None,
+            stash: String::new(),
+            completions: Vec::new(),
+            completion_idx: 0,
+        }
+    }
+
+    pub fn open(&mut self, initial: &str) {
+        self.input = Some(LineInput::new(initial));
+        self.history_pos = None;
+        self.completions.clear();
+    }
+
+    pub fn close(&mut self) {
+        self.input = None;
+        self.completions.clear();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.input.is_some()
+    }
+
+    /// The line being typed, if the command line is open.
+    pub fn text(&self) -> Option<String> {
+        self.input.as_ref().map(|x| x.text())
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Remembers a line that was run, dropping an identical older entry.
+    pub fn push_history(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        self.history.retain(|x| x != line);
+        self.history.push(String::from(line));
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+    }
+
+    fn set_text(&mut self, text: &str) {
+        if let Some(input) = &mut self.input {
+            input.set_text(text);
+        }
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        let text = self.input.as_ref().map(|x| x.text()).unwrap_or_default();
+        let pos = match (self.history_pos, older) {
+            (None, true) if !self.history.is_empty() => {
+                self.stash = text;
+                Some(self.history.len() - 1)
+            }
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < self.history.len() => Some(i + 1),
+            (Some(_), false) => None,
+            (None, _) => return,
+        };
+        self.history_pos = pos;
+        let line = match pos {
+            Some(i) => self.history[i].clone(),
+            None => std::mem::take(&mut self.stash),
+        };
+        self.set_text(&line);
+    }
+
+    fn cycle_completion(&mut self, forward: bool, complete: &dyn Fn(&str) -> Vec<String>) {
+        if self.completions.is_empty() {
+            let text = self.input.as_ref().map(|x| x.text()).unwrap_or_default();
+            self.completions = complete(&text);
+            if self.completions.is_empty() {
+                return;
+            }
+            self.completion_idx = if forward { 0 } else { self.completions.len() - 1 };
+        } else if forward {
+            self.completion_idx = (self.completion_idx + 1) % self.completions.len();
+        } else {
+            self.completion_idx =
+                (self.completion_idx + self.completions.len() - 1) % self.completions.len();
+        }
+        let line = self.completions[self.completion_idx].clone();
+        self.set_text(&line);
+        // A single candidate is final; the next Tab completes from there
+        if self.completions.len() == 1 {
+            self.completions.clear();
+        }
+    }
+
+    /// Handles a key while the command line is open.
+    ///
+    /// `complete` turns the current line into the list of completed lines
+    /// offered by Tab.
+    pub fn handle_key(&mut self, k: Key, complete: &dyn Fn(&str) -> Vec<String>) -> CommandLineOutcome {
+        let text = match &self.input {
+            Some(input) => input.text(),
+            None => return CommandLineOutcome::Cancel,
+        };
+        match k {
+            Key::Esc | Key::Ctrl('c') => {
+                self.close();
+                return CommandLineOutcome::Cancel;
+            }
+            Key::Backspace if text.is_empty() => {
+                self.close();
+                return CommandLineOutcome::Cancel;
+            }
+            Key::Char('\n') => {
+                self.close();
+                return CommandLineOutcome::Submit(text);
+            }
+            Key::Char('\t') => {
+                self.cycle_completion(true, complete);
+                return CommandLineOutcome::Pending;
+            }
+            Key::BackTab => {
+                self.cycle_completion(false, complete);
+                return CommandLineOutcome::Pending;
+            }
+            Key::Up | Key::Ctrl('p') => self.browse_history(true),
+            Key::Down | Key::Ctrl('n') => self.browse_history(false),
+            k => {
+                if let Some(input) = &mut self.input {
+                    input.handle_key(k);
+                }
+                self.history_pos = None;
+            }
+        }
+        self.completions.clear();
+        CommandLineOutcome::Pending
+    }
+
+    /// Draws the completion candidates on the line above `area`, if there
+    /// are several to choose from.
+    pub fn draw_completions(&self, f: &mut Frame<Backend>, area: &Rect) {
+        if self.completions.len() < 2 || area.y == 0 {
+            return;
+        }
+        let spans: Vec<Span> = self
+            .completions
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let word = line.rsplit(' ').next().unwrap_or(line);
+                let style = if i == self.completion_idx {
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                Span::styled(format!(" {} ", word), style)
+            })
+            .collect();
+        let rect = Rect::new(area.x, area.y - 1, area.width, 1);
+        f.render_widget(Clear, rect);
+        f.render_widget(Paragraph::new(Spans::from(spans)), rect);
+    }
+}
+
+impl Drawable for CommandLine {
+    fn draw(&mut self, f: &mut Frame<Backend>, size: &Rect) {
+        let input = match &self.input {
+            Some(input) => input,
+            None => return,
+        };
+        let text = format!("{}{}", self.prompt, input.text());
+        let cursor = (self.prompt.len() + input.cursor()).min(u16::MAX as usize) as u16;
+        // Scroll horizontally so the cursor is always visible
+        let scroll = cursor.saturating_add(1).saturating_sub(size.width);
+        f.render_widget(Paragraph::new(text.as_str()).scroll((0, scroll)), *size);
+        if size.width == 0 {
+            return;
+        }
+        f.set_cursor(size.x + cursor.saturating_sub(scroll), size.y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_completion(_: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[test]
+    fn history_browsing() {
+        let mut cmdline = CommandLine::default();
+        cmdline.push_history("cd /");
+        cmdline.push_history("set hidden");
+        cmdline.open("so");
+        cmdline.handle_key(Key::Up, &no_completion);
+        cmdline.handle_key(Key::Up, &no_completion);
+        cmdline.handle_key(Key::Down, &no_completion);
+        cmdline.handle_key(Key::Down, &no_completion);
+        match cmdline.handle_key(Key::Char('\n'), &no_completion) {
+            CommandLineOutcome::Submit(line) => assert_eq!(line, "so"),
+            _ => panic!("expected a submitted line"),
+        }
+        cmdline.open("");
+        cmdline.handle_key(Key::Up, &no_completion);
+        match cmdline.handle_key(Key::Char('\n'), &no_completion) {
+            CommandLineOutcome::Submit(line) => assert_eq!(line, "set hidden"),
+            _ => panic!("expected a submitted line"),
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use std::rc::Rc;
+
+use termion::event::Key;
+use tui::layout::{Alignment, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph};
+use tui::Frame;
+
+use crate::zeuslib::events::loopaction::EventLoopAction;
+use crate::zeuslib::state::State;
+use crate::zeuslib::ui::Drawable;
+use crate::zeuslib::Backend;
+
+pub type ConfirmCallback = Rc<dyn Fn(&mut State) -> EventLoopAction>;
+pub type InputCallback = Rc<dyn Fn(&mut State, String) -> EventLoopAction>;
+pub type ChooseCallback = Rc<dyn Fn(&mut State, usize) -> EventLoopAction>;
+
+const DIALOG_WIDTH: u16 = 60;
+const MAX_CHOICES_SHOWN: u16 = 12;
+const MAX_DETAILS_SHOWN: u16 = 16;
+
+/// A single line of editable text with a cursor.
+#[derive(Clone, Default)]
+pub struct LineInput {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl LineInput {
+    pub fn new(text: &str) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        let cursor = chars.len();
+        Self { chars, cursor }
+    }
+
+    pub fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    /// Moves the cursor to `pos`, clamped to the end of the text.
+    pub fn set_cursor(&mut self, pos: usize) {
+        self.cursor = pos.min(self.chars.len());
+    }
+
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && self.chars[i - 1] == ' ' {
+            i -= 1;
+        }
+        while i > 0 && self.chars[i - 1] != ' ' {
+            i -= 1;
+        }
+        i
+    }
+
+    /// Applies an editing key. Returns `false` if the key is not an editing key.
+    pub fn handle_key(&mut self, k: Key) -> bool {
+        match k {
+            Key::Char('\n') | Key::Char('\t') => return false,
+            Key::Char(c) => {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Backspace | Key::Ctrl('h') => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.chars.remove(self.cursor);
+                }
+            }
+            Key::Delete | Key::Ctrl('d') => {
+                if self.cursor < self.chars.len() {
+                    self.chars.remove(self.cursor);
+                }
+            }
+            Key::Left | Key::Ctrl('b') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right | Key::Ctrl('f') => self.cursor = (self.cursor + 1).min(self.chars.len()),
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = self.chars.len(),
+            Key::Ctrl('u') => {
+                self.chars.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::Ctrl('k') => self.chars.truncate(self.cursor),
+            Key::Ctrl('w') => {
+                let start = self.word_start();
+                self.chars.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+pub enum DialogKind {
+    Confirm {
+        on_yes: ConfirmCallback,
+        details: Vec<String>,
+    },
+    Input {
+        input: LineInput,
+        on_submit: InputCallback,
+    },
+    Choose {
+        items: Vec<String>,
+        selected: usize,
+        on_choose: ChooseCallback,
+    },
+    /// Information that any key dismisses.
+    Message {
+        lines: Vec<String>,
+    },
+}
+
+/// What handling a key did to a dialog.
+pub enum DialogOutcome {
+    /// The dialog stays open.
+    Pending(Dialog),
+    /// The dialog was answered and its callback should run.
+    Submit(Box<dyn FnOnce(&mut State) -> EventLoopAction>),
+    /// The dialog was dismissed without an answer.
+    Cancel,
+}
+
+/// A modal popup that receives all key input while it is open.
+pub struct Dialog {
+    pub title: String,
+    pub kind: DialogKind,
+}
+
+impl Dialog {
+    /// A yes/no question; `on_yes` runs if the user answers `y`.
+    pub fn confirm(title: &str, on_yes: ConfirmCallback) -> Self {
+        Self {
+            title: String::from(title),
+            kind: DialogKind::Confirm {
+                on_yes,
+                details: Vec::new(),
+            },
+        }
+    }
+
+    /// Adds lines shown above the yes/no answer of a confirmation dialog.
+    pub fn with_details(mut self, lines: Vec<String>) -> Self {
+        if let DialogKind::Confirm { details, .. } = &mut self.kind {
+            *details = lines;
+        }
+        self
+    }
+
+    /// A single-line text prompt pre-filled with `initial`.
+    pub fn input(title: &str, initial: &str, on_submit: InputCallback) -> Self {
+        Self {
+            title: String::from(title),
+            kind: DialogKind::Input {
+                input: LineInput::new(initial),
+                on_submit,
+            },
+        }
+    }
+
+    /// A list to pick one entry from; `on_choose` receives its index.
+    pub fn choose(title: &str, items: Vec<String>, on_choose: ChooseCallback) -> Self {
+        Self {
+            title: String::from(title),
+            kind: DialogKind::Choose {
+                items,
+                selected: 0,
+                on_choose,
+            },
+        }
+    }
+
+    /// Lines of information, closed by any key.
+    pub fn message(title: &str, lines: Vec<String>) -> Self {
+        Self {
+            title: String::from(title),
+            kind: DialogKind::Message { lines },
+        }
+    }
+
+    pub fn handle_key(mut self, k: Key) -> DialogOutcome {
+        if k == Key::Esc || k == Key::Ctrl('c') {
+            return DialogOutcome::Cancel;
+        }
+        match &mut self.kind {
+            DialogKind::Confirm { on_yes, .. } => match k {
+                Key::Char('y') | Key::Char('Y') => {
+                    let on_yes = Rc::clone(on_yes);
+                    DialogOutcome::Submit(Box::new(move |state| on_yes(state)))
+                }
+                _ => DialogOutcome::Cancel,
+            },
+            DialogKind::Input { input, on_submit } => {
+                if k == Key::Char('\n') {
+                    let text = input.text();
+                    let on_submit = Rc::clone(on_submit);
+                    return DialogOutcome::Submit(Box::new(move |state| on_submit(state, text)));
+                }
+                input.handle_key(k);
+                DialogOutcome::Pending(self)
+            }
+            DialogKind::Choose {
+                items,
+                selected,
+                on_choose,
+            } => {
+                match k {
+                    Key::Char('\n') if !items.is_empty() => {
+                        let i = *selected;
+                        let on_choose = Rc::clone(on_choose);
+                        return DialogOutcome::Submit(Box::new(move |state| on_choose(state, i)));
+                    }
+                    Key::Char('j') | Key::Down | Key::Ctrl('n') if *selected + 1 < items.len() => {
+                        *selected += 1;
+                    }
+                    Key::Char('k') | Key::Up | Key::Ctrl('p') => {
+                        *selected = selected.saturating_sub(1);
+                    }
+                    _ => {}
+                }
+                DialogOutcome::Pending(self)
+            }
+            DialogKind::Message { .. } => DialogOutcome::Cancel,
+        }
+    }
+
+    fn height(&self) -> u16 {
+        match &self.kind {
+            DialogKind::Confirm { details, .. } => {
+                (details.len() as u16).min(MAX_DETAILS_SHOWN) + 3
+            }
+            DialogKind::Input { .. } => 3,
+            DialogKind::Choose { items, .. } => (items.len() as u16).clamp(1, MAX_CHOICES_SHOWN) + 2,
+            DialogKind::Message { lines } => (lines.len() as u16).min(MAX_DETAILS_SHOWN) + 3,
+        }
+    }
+
+    fn width(&self) -> u16 {
+        match &self.kind {
+            DialogKind::Message { lines } => {
+                let longest = lines.iter().map(|x| x.chars().count()).max().unwrap_or(0);
+                (longest as u16 + 2).max(DIALOG_WIDTH)
+            }
+            _ => DIALOG_WIDTH,
+        }
+    }
+}
+
+/// Renders up to `max` of `lines` at the top of `inner`, ending with a
+/// "... and N more" line if some do not fit. Returns the height used.
+fn draw_lines(f: &mut Frame<Backend>, lines: &[String], max: u16, inner: Rect) -> u16 {
+    let shown = (lines.len() as u16).min(max);
+    let mut spans: Vec<Spans> = lines
+        .iter()
+        .take(shown as usize)
+        .map(|x| Spans::from(x.as_str()))
+        .collect();
+    if lines.len() > shown as usize {
+        let more = format!("... and {} more", lines.len() - shown as usize + 1);
+        spans.pop();
+        spans.push(Spans::from(more));
+    }
+    let rect = Rect::new(inner.x, inner.y, inner.width, shown);
+    f.render_widget(Paragraph::new(spans), rect);
+    shown
+}
+
+/// A rectangle of at most `width` x `height` centered in `area`.
+pub fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+impl Drawable for Dialog {
+    fn draw(&mut self, f: &mut Frame<Backend>, size: &Rect) {
+        let area = centered_rect(self.width(), self.height(), *size);
+        f.render_widget(Clear, area);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(self.title.as_str())
+            .border_style(Style::default().fg(Color::Yellow));
+        let inner = block.inner(area);
+        match &self.kind {
+            DialogKind::Confirm { details, .. } => {
+                f.render_widget(block, area);
+                if inner.height == 0 {
+                    return;
+                }
+                let shown = draw_lines(f, details, MAX_DETAILS_SHOWN.min(inner.height - 1), inner);
+
+                let answer = Spans::from(vec![
+                    Span::styled("y", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw("es / "),
+                    Span::styled("n", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw("o"),
+                ]);
+                let answer_rect = Rect::new(inner.x, inner.y + shown, inner.width, 1);
+                f.render_widget(Paragraph::new(answer).alignment(Alignment::Center), answer_rect);
+            }
+            DialogKind::Input { input, .. } => {
+                let text = input.text();
+                let cursor = input.cursor().min(u16::MAX as usize) as u16;
+                // Scroll horizontally so the cursor is always visible
+                let scroll = cursor.saturating_add(1).saturating_sub(inner.width);
+                let p = Paragraph::new(text.as_str()).block(block).scroll((0, scroll));
+                f.render_widget(p, area);
+                if inner.width == 0 {
+                    return;
+                }
+                f.set_cursor(inner.x + cursor.saturating_sub(scroll), inner.y);
+            }
+            DialogKind::Choose {
+                items, selected, ..
+            } => {
+                let list_items: Vec<ListItem> =
+                    items.iter().map(|x| ListItem::new(Span::raw(x.as_str()))).collect();
+                let list = List::new(list_items).block(block).highlight_style(
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::REVERSED),
+                );
+                let mut list_state = ListState::default();
+                list_state.select(Some(*selected));
+                f.render_stateful_widget(list, area, &mut list_state);
+            }
+            DialogKind::Message { lines } => {
+                f.render_widget(block, area);
+                if inner.height == 0 {
+                    return;
+                }
+                let shown = draw_lines(f, lines, MAX_DETAILS_SHOWN.min(inner.height - 1), inner);
+                let hint = Paragraph::new("Press any key")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .alignment(Alignment::Center);
+                f.render_widget(hint, Rect::new(inner.x, inner.y + shown, inner.width, 1));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_input_editing() {
+        let mut input = LineInput::new("hello world");
+        input.handle_key(Key::Ctrl('w'));
+        assert_eq!(input.text(), "hello ");
+        input.handle_key(Key::Home);
+        input.handle_key(Key::Char('>'));
+        input.handle_key(Key::Right);
+        input.handle_key(Key::Delete);
+        assert_eq!(input.text(), ">hllo ");
+        assert_eq!(input.cursor(), 2);
+        input.handle_key(Key::Ctrl('k'));
+        input.handle_key(Key::Backspace);
+        assert_eq!(input.text(), ">");
+        assert!(!input.handle_key(Key::Char('\n')));
+    }
+}
@@ -1,52 +1,60 @@
-use tui::widgets::{ListState, ListItem, List, Borders, Block, StatefulWidget};
+use tui::widgets::{ListState, ListItem, List, Borders, Block};
 use tui::{Frame};
-use tui::{text::Span};
+use tui::text::{Span, Spans};
 use tui::layout::{Rect};
-use tui::buffer::{Buffer};
 pub use tui::style::{Style, Color, Modifier};
 
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::iter::FromIterator;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
 
 pub use std::rc::Rc;
 pub use std::cell::RefCell;
 
 use crate::zeuslib::Backend;
-use crate::zeuslib::utils::fs::FileSize;
+use crate::zeuslib::events::EventSender;
+use crate::zeuslib::listing::{spawn_listing, ListUpdate};
+use crate::zeuslib::utils::fs::info::FileMeta;
+use crate::zeuslib::utils::fs::{display_name, display_path, FileSize};
+use crate::zeuslib::utils::matcher::Matcher;
 pub use crate::zeuslib::ui::Drawable;
 
 #[derive(Clone)]
 #[derive(Debug)]
 pub struct FileListItem {
-    pub path: String,
+    pub path: PathBuf,
     pub marked: bool,
+    /// Read when the item is created, i.e. when the list is refreshed.
+    pub meta: FileMeta,
 }
 
 
 impl FileListItem {
-    pub fn new(path: String) -> Self {
+    pub fn new(path: PathBuf) -> Self {
+        let meta = FileMeta::read(&path);
         Self {
             path,
-            marked: false
+            marked: false,
+            meta,
         }
     }
 
     pub fn is_dir(&self) -> bool {
-        let p = Path::new(&self.path);
-        p.is_dir()
+        self.meta.is_dir
     }
 
     pub fn is_file(&self) -> bool {
-        let p = Path::new(&self.path);
-        p.is_file()
+        self.meta.is_file
     }
     fn get_size_str(&self) -> String {
-        let p = Path::new(&self.path);
-        if p.is_dir() {
+        if self.meta.is_dir {
             String::from("DIR")
-        } else if let Ok(meta) = p.metadata() {
-            let l = meta.len();
-            format!("{}", FileSize::from_total_bytes(l))
+        } else if self.meta.is_file || self.meta.is_symlink {
+            format!("{}", FileSize::from_total_bytes(self.meta.size))
         } else {
             String::from("")
         }
@@ -66,26 +74,21 @@ impl FileListItem {
             }
         }
     }
-    fn get_text(&self, width: u16) -> String {
-        let name = Path::new(&self.path).file_name().unwrap().to_str().unwrap();
-        let base = if self.is_dir() {
-            format!(" [{}]", name)  
-        } else {
-            format!(" {}", name)
-        };
-
-        let base = if self.marked {
-            format!(">{}", base.trim_start())
-        } else {
-            base
-        };
+    /// The line shown for this item: its name, with the parts matching
+    /// `highlight` set off, and its size at the right edge.
+    fn get_text(&self, width: u16, highlight: Option<&Matcher>) -> Spans<'static> {
+        let name = self.display_name();
+        let name = name.as_str();
+        let (open, close) = if self.is_dir() { ("[", "]") } else { ("", "") };
+        let lead = if self.marked { ">" } else { " " };
 
         let size_text = self.get_size_str();
+        let base_len = lead.len() + open.len() + name.chars().count() + close.len();
         let width: usize = width.into();
         let pad_width = {
             let mut w = width;
-            if w > base.len() + 1 {
-                w -= base.len() + 1;
+            if w > base_len + 1 {
+                w -= base_len + 1;
             } else {
                 w = 0;
             }
@@ -96,10 +99,70 @@ impl FileListItem {
             }
             w.max(1)
         };
-        
+
         let padding = String::from_iter((0 .. pad_width).map(|_| ' '));
 
-        format!("{}{}{}", base, padding, size_text)
+        let mut spans = vec![Span::raw(format!("{}{}", lead, open))];
+        let mut last = 0;
+        for range in highlight.map(|m| m.find_ranges(name)).unwrap_or_default() {
+            spans.push(Span::raw(String::from(&name[last..range.start])));
+            spans.push(Span::styled(
+                String::from(&name[range.clone()]),
+                Style::default().fg(Color::Black).bg(Color::Yellow),
+            ));
+            last = range.end;
+        }
+        spans.push(Span::raw(format!("{}{}{}{}", &name[last..], close, padding, size_text)));
+        Spans::from(spans)
+    }
+
+    /// The file name, without the directory. Bytes that are not UTF-8 are
+    /// replaced, so this is for matching and showing, not for finding the file.
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// The file name as shown in the list, with control characters escaped.
+    pub fn display_name(&self) -> String {
+        self.path.file_name().map(display_name).unwrap_or_default()
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.path
+            .file_name()
+            .map(|x| x.to_string_lossy().starts_with('.'))
+            .unwrap_or(false)
+    }
+
+    /// Orders items by `key`, always keeping directories before files.
+    fn order_by(a: &FileListItem, b: &FileListItem, key: SortKey, reverse: bool) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+        let a_dir = a.is_dir();
+        let b_dir = b.is_dir();
+        if a_dir != b_dir {
+            return if a_dir { Ordering::Less } else { Ordering::Greater };
+        }
+        let ord = match key {
+            SortKey::Name => Self::order(a, b),
+            SortKey::Size => {
+                a.meta.size.cmp(&b.meta.size).then_with(|| Self::order(a, b))
+            }
+            SortKey::Mtime => {
+                // Newest first, like `ls -t`
+                b.meta.mtime.cmp(&a.meta.mtime).then_with(|| Self::order(a, b))
+            }
+            SortKey::Ext => {
+                a.path.extension().cmp(&b.path.extension()).then_with(|| Self::order(a, b))
+            }
+        };
+        if reverse {
+            ord.reverse()
+        } else {
+            ord
+        }
     }
 
     fn order(a: &FileListItem, b: &FileListItem) -> std::cmp::Ordering {
@@ -125,58 +188,179 @@ impl FileListItem {
 
 
 
+/// What a file list is sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Mtime,
+    Ext,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 4] = [SortKey::Name, SortKey::Size, SortKey::Mtime, SortKey::Ext];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|k| k.as_str() == s)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Mtime => "mtime",
+            SortKey::Ext => "ext",
+        }
+    }
+}
+
+/// Settings deciding which entries a file list shows, in what order, and
+/// how the cursor moves through them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ListOptions {
+    pub show_hidden: bool,
+    pub sort: SortKey,
+    pub sort_reverse: bool,
+    /// Rows kept visible above and below the cursor when scrolling.
+    pub scrolloff: usize,
+    /// Whether moving past the last entry goes to the first and back.
+    pub wrap_scroll: bool,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            show_hidden: true,
+            sort: SortKey::Name,
+            sort_reverse: false,
+            scrolloff: 0,
+            wrap_scroll: true,
+        }
+    }
+}
+
+/// Merges two lists sorted by `order` into one.
+fn merge_sorted<F>(a: Vec<FileListItem>, b: Vec<FileListItem>, order: F) -> Vec<FileListItem>
+where
+    F: Fn(&FileListItem, &FileListItem) -> std::cmp::Ordering,
+{
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+    loop {
+        let from_a = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) => order(x, y) != std::cmp::Ordering::Greater,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => return merged,
+        };
+        merged.extend(if from_a { a.next() } else { b.next() });
+    }
+}
+
+/// Source of `FileList::id`.
+static NEXT_LIST_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A read of the root that is still running on a worker thread.
+#[derive(Clone, Debug)]
+struct Loading {
+    id: usize,
+    cancelled: Arc<AtomicBool>,
+    /// Entries read so far when the root is read again; the old listing is
+    /// shown until they are complete. `None` if they go straight into the
+    /// list.
+    pending: Option<Vec<FileListItem>>,
+    /// A path to put the cursor on once it has been read.
+    select: Option<PathBuf>,
+    /// Entries read so far, hidden and filtered ones included.
+    count: usize,
+}
+
 #[allow(dead_code)]
 #[derive(Clone)]
 #[derive(Debug)]
 pub struct FileList {
     pub state: ListState,
     pub items: Vec<FileListItem>,
+    /// Every entry read from the root, hidden and filtered ones included, so
+    /// that changing what is listed does not read it again.
+    entries: Vec<FileListItem>,
     pub cursor_pos: usize,
-    root: String,
-}
-
-impl StatefulWidget for FileList {
-    type State = ListState;
-    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State)
-    {
-        let w = area.width;
-        let items: Vec<ListItem> = self.items
-            .iter()
-            .map(|x| {
-                ListItem::new(Span::raw(x.get_text(w))).style(x.get_style())
-            }).collect();
-
-        let items = List::new(items)
-            .block(Block::default().borders(Borders::ALL).title(self.root.clone()))
-            .highlight_style(
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::REVERSED),
-                );
-        items.render(area, buf, state);
-    }
+    pub options: ListOptions,
+    root: PathBuf,
+    /// Index of the first visible item.
+    offset: usize,
+    /// Number of items that fit when last drawn.
+    height: usize,
+    /// The last search, whose matches are highlighted.
+    pub highlight: Option<Matcher>,
+    /// Only entries whose name matches are listed.
+    pub filter: Option<Matcher>,
+    /// Tells the reports of this list's directory reads apart from those
+    /// of other lists.
+    id: usize,
+    /// Where directory reads report to. Without one the root is read on the
+    /// spot.
+    sender: Option<EventSender>,
+    loading: Option<Loading>,
+    next_load: usize,
+    /// Whether the list has the cursor, i.e. `select` was called more
+    /// recently than `unselect`.
+    focused: bool,
 }
 
 #[allow(dead_code)]
 impl FileList {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &Path) -> Self {
         Self::with_items(&[], root)
     }
 
-    pub fn with_items(items: &[FileListItem], root: &str) -> Self {
+    pub fn with_items(items: &[FileListItem], root: &Path) -> Self {
         Self {
             state: ListState::default(),
             items: Vec::from(items),
-            root: String::from(root),
+            entries: Vec::from(items),
+            root: root.to_path_buf(),
             cursor_pos: 0,
+            options: ListOptions::default(),
+            offset: 0,
+            height: 0,
+            highlight: None,
+            filter: None,
+            id: NEXT_LIST_ID.fetch_add(1, Ordering::Relaxed),
+            sender: None,
+            loading: None,
+            next_load: 0,
+            focused: false,
         }
     }
 
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Makes `refresh_list` read directories on a worker thread, reporting
+    /// to `sender`.
+    pub fn set_sender(&mut self, sender: EventSender) {
+        self.sender = Some(sender);
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading.is_some()
+    }
+
     pub fn set_items(&mut self, items: &[FileListItem]) {
         self.items = Vec::from(items);
+        self.entries = Vec::from(items);
     }
 
     pub fn select(&mut self, index: usize) {
+        self.focused = true;
+        if self.items.is_empty() {
+            self.cursor_pos = 0;
+            self.state.select(None);
+            return;
+        }
         self.cursor_pos = index.min(self.items.len() - 1);
         self.state.select(Some(self.cursor_pos));
     }
@@ -184,45 +368,217 @@ impl FileList {
         self.state.selected()
     }
     pub fn selected_item(&self) -> Option<FileListItem> {
-        if let Some(sel) = self.selected() {
-            Some(self.items[sel].clone())
+        self.selected().map(|sel| self.items[sel].clone())
+    }
+
+    /// Moves the cursor onto the item with the given path, if it is listed.
+    /// While the root is being read, a path that has not turned up yet gets
+    /// the cursor when it does.
+    pub fn select_path(&mut self, path: &Path) -> bool {
+        if let Some(i) = self.items.iter().position(|x| x.path == path) {
+            self.select(i);
+            if let Some(loading) = &mut self.loading {
+                loading.select = None;
+            }
+            true
         } else {
-            None
+            if let Some(loading) = &mut self.loading {
+                loading.select = Some(path.to_path_buf());
+            }
+            false
         }
     }
 
+    /// Reads the directory again, applying the filter and list options. A
+    /// selected item that is still listed keeps the cursor. Use `relist`
+    /// when only the filter or options have changed.
+    ///
+    /// With a sender the directory is read on a worker thread, and a read
+    /// still running is cancelled. A new root is listed as its entries come
+    /// in; the same root keeps its old listing until the read is complete.
     pub fn refresh_list(&mut self) {
-        let p = Path::new(&self.root);
-        if !p.is_dir() {
+        if !self.root.is_dir() {
             return;
         }
+        self.cancel_loading();
+        let sender = match &self.sender {
+            Some(sender) => sender.clone(),
+            None => {
+                let items = self.root.read_dir().map(|dir| {
+                    dir.flatten().map(|entry| FileListItem::new(entry.path())).collect()
+                });
+                self.replace_items(items.unwrap_or_default(), None);
+                return;
+            }
+        };
+        let id = self.next_load;
+        self.next_load += 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.loading = Some(Loading {
+            id,
+            cancelled: cancelled.clone(),
+            pending: if self.items.is_empty() { None } else { Some(Vec::new()) },
+            select: None,
+            count: 0,
+        });
+        spawn_listing(self.root.clone(), self.id, id, cancelled, sender);
+    }
+
+    /// Stops the read of the root that is running, if any.
+    pub fn cancel_loading(&mut self) {
+        if let Some(loading) = self.loading.take() {
+            loading.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
 
-        self.items.clear();
-        if let Ok(dir) = p.read_dir() {
-            for entry in dir {
-                if let Ok(entry) = entry {
-                    let p = entry.path();
-                    if let Some(p) = p.to_str() {
-                        self.items.push(FileListItem::new(String::from(p)));
+    /// Takes in a report of the read started by `refresh_list`; those of
+    /// earlier reads are ignored. Returns whether another entry now has the
+    /// cursor.
+    pub fn receive(&mut self, load_id: usize, update: ListUpdate) -> bool {
+        let before = self.selected_item().map(|x| x.path);
+        let loading = match &mut self.loading {
+            Some(loading) if loading.id == load_id => loading,
+            _ => return false,
+        };
+        match update {
+            ListUpdate::Entries(entries) => {
+                loading.count += entries.len();
+                match &mut loading.pending {
+                    Some(pending) => pending.extend(entries),
+                    None => self.add_items(entries),
+                }
+            }
+            ListUpdate::Finished => {
+                let loading = self.loading.take().unwrap();
+                match loading.pending {
+                    Some(pending) => self.replace_items(pending, loading.select),
+                    None => {
+                        if self.focused && self.selected().is_none() {
+                            self.select(self.cursor_pos);
+                        }
                     }
                 }
             }
         }
-        self.items.sort_by(FileListItem::order);
+        self.selected_item().map(|x| x.path) != before
+    }
+
+    /// The items that pass the hidden file option and the filter.
+    fn listed(&self, mut items: Vec<FileListItem>) -> Vec<FileListItem> {
+        if !self.options.show_hidden {
+            items.retain(|x| !x.is_hidden());
+        }
+        if let Some(filter) = &self.filter {
+            items.retain(|x| filter.is_match(&x.name()));
+        }
+        items
+    }
+
+    fn sort(&mut self) {
+        let ListOptions { sort, sort_reverse, .. } = self.options;
+        self.items
+            .sort_by(|a, b| FileListItem::order_by(a, b, sort, sort_reverse));
+    }
+
+    /// Lists `items` instead of the current ones, keeping the marks of
+    /// entries that are still listed. The cursor goes to `select`, or else
+    /// stays on the same entry or position.
+    fn replace_items(&mut self, items: Vec<FileListItem>, select: Option<PathBuf>) {
+        self.entries = items;
+        self.list_entries(select);
+    }
+
+    /// Applies the filter and list options to the entries already read,
+    /// keeping marks and the cursor as `refresh_list` does.
+    pub fn relist(&mut self) {
+        self.list_entries(None);
+    }
+
+    fn list_entries(&mut self, select: Option<PathBuf>) {
+        let selected = select.or_else(|| self.selected_item().map(|x| x.path));
+        let marked: HashSet<PathBuf> =
+            self.items.iter().filter(|x| x.marked).map(|x| x.path.clone()).collect();
+        self.items = self.listed(self.entries.clone());
+        if !marked.is_empty() {
+            for item in self.items.iter_mut() {
+                item.marked = marked.contains(&item.path);
+            }
+        }
+        self.sort();
+        if let Some(path) = selected {
+            if !self.select_path(&path) {
+                self.select(self.cursor_pos);
+            }
+        }
+    }
+
+    /// Adds entries of a root being read, keeping the cursor on the entry it
+    /// is on unless a path given to `select_path` has turned up.
+    fn add_items(&mut self, items: Vec<FileListItem>) {
+        let wanted = self.loading.as_ref().and_then(|x| x.select.clone());
+        let selected = self.selected_item().map(|x| x.path);
+        self.entries.extend(items.iter().cloned());
+        // Only the new entries are sorted; the list already is
+        let ListOptions { sort, sort_reverse, .. } = self.options;
+        let order = |a: &FileListItem, b: &FileListItem| {
+            FileListItem::order_by(a, b, sort, sort_reverse)
+        };
+        let mut items = self.listed(items);
+        items.sort_by(order);
+        self.items = merge_sorted(std::mem::take(&mut self.items), items, order);
+        if let Some(path) = wanted {
+            if self.select_path(&path) {
+                return;
+            }
+        }
+        match selected.and_then(|path| self.items.iter().position(|x| x.path == path)) {
+            Some(i) => self.select(i),
+            None if self.focused => self.select(self.cursor_pos),
+            None => {}
+        }
+    }
+
+    /// The root, followed by the filter when there is one and by how many
+    /// entries have been read while the root is being read.
+    pub fn title(&self) -> String {
+        let mut title = display_path(&self.root);
+        if let Some(filter) = &self.filter {
+            title.push_str(&format!(" [filter: {}]", filter.as_str()));
+        }
+        if let Some(loading) = &self.loading {
+            title.push_str(&format!(" (loading… {})", loading.count));
+        }
+        title
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
     }
 
-    pub fn set_root(&mut self, root: &str) {
-        self.root = String::from(root);
+    /// Lists `root`. The entries of another directory are dropped at once
+    /// rather than shown under the new root while it is read.
+    pub fn set_root(&mut self, root: &Path) {
+        if self.root != root {
+            self.root = root.to_path_buf();
+            self.items.clear();
+            self.entries.clear();
+            self.state.select(None);
+        }
         self.refresh_list();
     }
 
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
-                if i >= self.items.len() - 1 {
+                if i < self.items.len() - 1 {
+                    i + 1
+                } else if self.options.wrap_scroll {
                     0
                 } else {
-                    i + 1
+                    i
                 }
             }
             None => 0,
@@ -232,12 +588,17 @@ impl FileList {
     }
 
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
-                if i == 0 {
+                if i > 0 {
+                    i - 1
+                } else if self.options.wrap_scroll {
                     self.items.len() - 1
                 } else {
-                    i - 1
+                    i
                 }
             }
             None => 0,
@@ -246,16 +607,295 @@ impl FileList {
         self.cursor_pos = i;
     }
 
+    /// Moves the cursor `delta` items down, or up if negative, stopping at
+    /// the first and last item.
+    pub fn move_by(&mut self, delta: isize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let selected = self.state.selected().unwrap_or(0);
+        let i = if delta < 0 {
+            selected.saturating_sub(delta.unsigned_abs())
+        } else {
+            selected.saturating_add(delta as usize)
+        };
+        self.select(i);
+    }
+
+    /// The nearest item whose name matches, searching down or up from `from`
+    /// and wrapping around at the ends. `from` itself is checked first if
+    /// `inclusive`, otherwise last.
+    pub fn find_match(
+        &self,
+        matcher: &Matcher,
+        from: usize,
+        forward: bool,
+        inclusive: bool,
+    ) -> Option<usize> {
+        let n = self.items.len();
+        if n == 0 {
+            return None;
+        }
+        let from = from % n;
+        let first = if inclusive { 0 } else { 1 };
+        (first..first + n)
+            .map(|d| if forward { (from + d) % n } else { (from + n - d % n) % n })
+            .find(|&i| matcher.is_match(&self.items[i].name()))
+    }
+
+    /// Rows moved by a page, as last drawn.
+    fn page(&self) -> usize {
+        self.height.max(1)
+    }
+
+    /// `count` times `rows`, as a distance to move down; any larger than the
+    /// list is as good as the end of it.
+    fn rows_by(rows: usize, count: usize) -> isize {
+        rows.saturating_mul(count).min(isize::MAX as usize) as isize
+    }
+
+    pub fn page_down(&mut self, count: usize) {
+        self.move_by(Self::rows_by(self.page(), count));
+    }
+
+    pub fn page_up(&mut self, count: usize) {
+        self.move_by(-Self::rows_by(self.page(), count));
+    }
+
+    pub fn half_page_down(&mut self, count: usize) {
+        self.move_by(Self::rows_by((self.page() / 2).max(1), count));
+    }
+
+    pub fn half_page_up(&mut self, count: usize) {
+        self.move_by(-Self::rows_by((self.page() / 2).max(1), count));
+    }
+
+    pub fn go_top(&mut self) {
+        self.select(0);
+    }
+
+    pub fn go_bottom(&mut self) {
+        self.select(self.items.len().saturating_sub(1));
+    }
+
+    /// Moves the cursor to the middle of the visible items.
+    pub fn go_middle(&mut self) {
+        let visible = self.items.len().saturating_sub(self.offset).min(self.page());
+        self.select(self.offset + visible.saturating_sub(1) / 2);
+    }
+
+    /// The first item to show at `height` rows so the cursor stays visible
+    /// with `scrolloff` rows around it, scrolling as little as possible.
+    fn scroll_offset(&self, height: usize) -> usize {
+        let cursor = self.selected().unwrap_or(0);
+        if height == 0 {
+            return cursor;
+        }
+        let margin = self.options.scrolloff.min(height.saturating_sub(1) / 2);
+        let mut offset = self.offset;
+        if cursor < offset + margin {
+            offset = cursor.saturating_sub(margin);
+        }
+        if cursor + margin >= offset + height {
+            offset = cursor + margin + 1 - height;
+        }
+        offset.min(self.items.len().saturating_sub(height))
+    }
+
     pub fn unselect(&mut self) {
+        self.focused = false;
         self.state.select(None);
     }
 
 }
 
 impl Drawable for FileList {
+    /// Draws only the visible items, keeping the scroll position in `offset`.
     fn draw(&mut self, f: &mut Frame<Backend>, size: &Rect) {
-        f.render_stateful_widget(self.clone(), *size, &mut self.state);
+        // Rows inside the border
+        self.height = size.height.saturating_sub(2) as usize;
+        self.offset = self.scroll_offset(self.height);
+
+        let w = size.width;
+        let items: Vec<ListItem> = self
+            .items
+            .iter()
+            .skip(self.offset)
+            .take(self.height)
+            .map(|x| ListItem::new(x.get_text(w, self.highlight.as_ref())).style(x.get_style()))
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(self.title()))
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::REVERSED),
+            );
+        let mut state = ListState::default();
+        state.select(self.selected().map(|i| i.saturating_sub(self.offset)));
+        f.render_stateful_widget(list, *size, &mut state);
     }
 }
 
 pub type FileListRc = Option<Rc<RefCell<FileList>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zeuslib::utils::testing::TempDir;
+
+    fn list(n: usize) -> FileList {
+        let items: Vec<FileListItem> = (0..n)
+            .map(|i| FileListItem::new(PathBuf::from(format!("/tmp/{}", i))))
+            .collect();
+        let mut list = FileList::with_items(&items, Path::new("/tmp"));
+        list.select(0);
+        list.height = 10;
+        list
+    }
+
+    #[test]
+    fn scrolling_keeps_a_margin() {
+        let mut list = list(100);
+        list.options.scrolloff = 3;
+        list.select(7);
+        assert_eq!(list.scroll_offset(10), 1);
+        list.offset = 1;
+        list.select(2);
+        assert_eq!(list.scroll_offset(10), 0);
+        list.select(99);
+        assert_eq!(list.scroll_offset(10), 90);
+    }
+
+    #[test]
+    fn paging_and_wrapping() {
+        let mut list = list(100);
+        list.half_page_down(3);
+        assert_eq!(list.selected(), Some(15));
+        list.page_down(20);
+        assert_eq!(list.selected(), Some(99));
+        list.next();
+        assert_eq!(list.selected(), Some(0));
+        list.options.wrap_scroll = false;
+        list.previous();
+        assert_eq!(list.selected(), Some(0));
+        list.go_bottom();
+        list.offset = 90;
+        list.go_middle();
+        assert_eq!(list.selected(), Some(94));
+    }
+
+    #[test]
+    fn huge_moves_stop_at_the_ends() {
+        let mut list = list(100);
+        list.select(50);
+        list.move_by(isize::MAX);
+        assert_eq!(list.selected(), Some(99));
+        list.move_by(isize::MIN);
+        assert_eq!(list.selected(), Some(0));
+        list.page_down(usize::MAX);
+        assert_eq!(list.selected(), Some(99));
+        list.half_page_up(usize::MAX);
+        assert_eq!(list.selected(), Some(0));
+    }
+
+    #[test]
+    fn batches_are_merged_in_order() {
+        let items = |names: &[&str]| -> Vec<FileListItem> {
+            names.iter().map(|x| FileListItem::new(PathBuf::from(format!("/none/{}", x)))).collect()
+        };
+        let mut list = FileList::new(Path::new("/none"));
+        list.add_items(items(&["d", "b"]));
+        list.add_items(items(&["c", "a", "e"]));
+        let names: Vec<String> = list.items.iter().map(|x| x.name()).collect();
+        assert_eq!(names, vec!["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn find_matches() {
+        use crate::zeuslib::utils::matcher::MatchKind;
+        let list = list(30);
+        let m = Matcher::new("^2", MatchKind::Regex, false).unwrap();
+        assert_eq!(list.find_match(&m, 2, true, true), Some(2));
+        assert_eq!(list.find_match(&m, 2, true, false), Some(20));
+        assert_eq!(list.find_match(&m, 29, true, false), Some(2));
+        assert_eq!(list.find_match(&m, 20, false, false), Some(2));
+        assert_eq!(list.find_match(&m, 2, false, false), Some(29));
+    }
+
+    #[test]
+    fn filter_keeps_the_selected_item() {
+        use crate::zeuslib::utils::matcher::MatchKind;
+        let tmp = TempDir::new("filter");
+        tmp.touch(&["a.rs", "b.txt", "c.rs"]);
+        let mut list = FileList::new(tmp.path());
+        list.refresh_list();
+        list.select(2);
+        list.filter = Some(Matcher::new("*.rs", MatchKind::Glob, false).unwrap());
+        // Listed again from what was read, not from the disk
+        tmp.touch(&["d.rs"]);
+        list.relist();
+        let names: Vec<String> = list.items.iter().map(|x| x.name()).collect();
+        assert_eq!(names, vec!["a.rs", "c.rs"]);
+        assert_eq!(list.selected_item().map(|x| x.name()).as_deref(), Some("c.rs"));
+        list.filter = None;
+        list.relist();
+        assert_eq!(list.items.len(), 3);
+        list.refresh_list();
+        assert_eq!(list.items.len(), 4);
+    }
+
+    #[test]
+    fn directories_are_read_in_the_background() {
+        use crate::zeuslib::events::Event;
+        let tmp = TempDir::new("listing");
+        tmp.touch(&["a", "b", "c"]);
+        let dir = tmp.path();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut list = FileList::new(dir);
+        list.set_sender(tx);
+        let receive_all = |list: &mut FileList| {
+            while list.is_loading() {
+                match rx.recv_timeout(std::time::Duration::from_secs(5)) {
+                    Ok(Event::List(evt)) => {
+                        list.receive(evt.load_id, evt.update);
+                    }
+                    Ok(_) => {}
+                    Err(_) => panic!("listing did not finish"),
+                }
+            }
+        };
+
+        list.refresh_list();
+        list.select_path(&dir.join("b"));
+        assert!(list.title().contains("loading"));
+        receive_all(&mut list);
+        let names: Vec<String> = list.items.iter().map(|x| x.name()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert_eq!(list.selected_item().map(|x| x.name()).as_deref(), Some("b"));
+
+        // Read again, the old listing stays until the new one is complete
+        std::fs::remove_file(dir.join("a")).unwrap();
+        list.refresh_list();
+        assert_eq!(list.items.len(), 3);
+        receive_all(&mut list);
+        assert_eq!(list.items.len(), 2);
+        assert_eq!(list.selected_item().map(|x| x.name()).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn non_utf8_names_are_listed() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let tmp = TempDir::new("latin1");
+        let dir = tmp.path();
+        let name = OsStr::from_bytes(b"caf\xe9\n.txt");
+        std::fs::write(dir.join(name), "").unwrap();
+        let mut list = FileList::new(dir);
+        list.refresh_list();
+        assert_eq!(list.items.len(), 1);
+        assert_eq!(list.items[0].path, dir.join(name));
+        assert_eq!(list.items[0].display_name(), "caf\u{fffd}\\n.txt");
+        assert!(list.items[0].is_file());
+    }
+}
@@ -0,0 +1,235 @@
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use termion::event::Key;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph};
+use tui::Frame;
+
+use crate::zeuslib::ui::dialog::LineInput;
+use crate::zeuslib::utils::fs::display_path;
+use crate::zeuslib::utils::fuzzy::{fuzzy_match, FuzzyMatch};
+use crate::zeuslib::utils::matcher::ignores_case;
+use crate::zeuslib::Backend;
+
+/// What the finder wants done after a key.
+pub enum FinderOutcome {
+    Pending,
+    Cancel,
+    Select(PathBuf),
+}
+
+/// Picker over every path below a directory, narrowed down by a fuzzy
+/// pattern. Paths arrive in batches while the tree is still being walked.
+pub struct FinderView {
+    root: PathBuf,
+    /// Every path found so far, relative to `root`, with how it is shown
+    /// and matched.
+    paths: Vec<(PathBuf, String)>,
+    /// Indices into `paths` of those matching the pattern, best first.
+    matches: Vec<(usize, FuzzyMatch)>,
+    /// The pattern `matches` is for.
+    matched: String,
+    input: LineInput,
+    ignore_case: bool,
+    smart_case: bool,
+    selected: usize,
+    scroll: usize,
+    finished: bool,
+}
+
+impl FinderView {
+    pub fn new(root: &Path, ignore_case: bool, smart_case: bool) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            paths: Vec::new(),
+            matches: Vec::new(),
+            matched: String::new(),
+            input: LineInput::new(""),
+            ignore_case,
+            smart_case,
+            selected: 0,
+            scroll: 0,
+            finished: false,
+        }
+    }
+
+    fn match_path(&self, path: &str) -> Option<FuzzyMatch> {
+        let pattern = self.input.text();
+        let ignore_case = ignores_case(&pattern, self.ignore_case, self.smart_case);
+        fuzzy_match(&pattern, path, ignore_case)
+    }
+
+    /// Best score first; among equals shorter paths, then the walk order.
+    fn rank(&self, (a, ma): &(usize, FuzzyMatch), (b, mb): &(usize, FuzzyMatch)) -> Ordering {
+        mb.score
+            .cmp(&ma.score)
+            .then_with(|| self.paths[*a].1.len().cmp(&self.paths[*b].1.len()))
+            .then_with(|| a.cmp(b))
+    }
+
+    /// Adds paths found by the walk. Only they are matched, and their
+    /// matches merged into the ranked ones.
+    pub fn add(&mut self, paths: Vec<PathBuf>) {
+        let mut found: Vec<(usize, FuzzyMatch)> = Vec::new();
+        for path in paths {
+            let rel = path.strip_prefix(&self.root).unwrap_or(&path).to_path_buf();
+            let shown = display_path(&rel);
+            if let Some(m) = self.match_path(&shown) {
+                found.push((self.paths.len(), m));
+            }
+            self.paths.push((rel, shown));
+        }
+        found.sort_by(|a, b| self.rank(a, b));
+        let mut merged = Vec::with_capacity(self.matches.len() + found.len());
+        let mut old = std::mem::take(&mut self.matches).into_iter().peekable();
+        let mut found = found.into_iter().peekable();
+        loop {
+            let from_old = match (old.peek(), found.peek()) {
+                (Some(a), Some(b)) => self.rank(a, b) != Ordering::Greater,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            merged.extend(if from_old { old.next() } else { found.next() });
+        }
+        self.matches = merged;
+    }
+
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Matches the paths against a changed pattern. A pattern that only
+    /// grew can only match paths the shorter one did, so only those are
+    /// tried again.
+    fn rematch(&mut self) {
+        let pattern = self.input.text();
+        let candidates: Vec<usize> = if pattern.starts_with(&self.matched) {
+            self.matches.iter().map(|(i, _)| *i).collect()
+        } else {
+            (0..self.paths.len()).collect()
+        };
+        let mut matches: Vec<(usize, FuzzyMatch)> = candidates
+            .into_iter()
+            .filter_map(|i| self.match_path(&self.paths[i].1).map(|m| (i, m)))
+            .collect();
+        matches.sort_by(|a, b| self.rank(a, b));
+        self.matches = matches;
+        self.matched = pattern;
+        self.selected = 0;
+    }
+
+    pub fn handle_key(&mut self, k: Key) -> FinderOutcome {
+        match k {
+            Key::Esc => return FinderOutcome::Cancel,
+            Key::Char('\n') => {
+                if let Some((i, _)) = self.matches.get(self.selected) {
+                    return FinderOutcome::Select(self.root.join(&self.paths[*i].0));
+                }
+            }
+            Key::Down | Key::Ctrl('n') => {
+                self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1));
+            }
+            Key::Up | Key::Ctrl('p') => self.selected = self.selected.saturating_sub(1),
+            k => {
+                let before = self.input.text();
+                self.input.handle_key(k);
+                if self.input.text() != before {
+                    self.rematch();
+                }
+            }
+        }
+        FinderOutcome::Pending
+    }
+
+    fn title(&self) -> String {
+        let walking = if self.finished { "" } else { " ..." };
+        format!(
+            "Find in {} ({}/{}{})",
+            display_path(&self.root),
+            self.matches.len(),
+            self.paths.len(),
+            walking
+        )
+    }
+
+    pub fn draw(&mut self, f: &mut Frame<Backend>, area: Rect) {
+        f.render_widget(Clear, area);
+        let block = Block::default().borders(Borders::ALL).title(self.title());
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        if inner.height < 2 {
+            return;
+        }
+        let prompt_area = Rect::new(inner.x, inner.y, inner.width, 1);
+        let list_area = Rect::new(inner.x, inner.y + 1, inner.width, inner.height - 1);
+
+        let page = list_area.height as usize;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + page {
+            self.scroll = self.selected + 1 - page;
+        }
+
+        let match_style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        let lines: Vec<Spans> = self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(page)
+            .map(|(row, (i, m))| {
+                let base = if row == self.selected {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                let spans: Vec<Span> = self.paths[*i]
+                    .1
+                    .chars()
+                    .enumerate()
+                    .map(|(ci, c)| {
+                        if m.positions.contains(&ci) {
+                            Span::styled(c.to_string(), base.patch(match_style))
+                        } else {
+                            Span::styled(c.to_string(), base)
+                        }
+                    })
+                    .collect();
+                Spans::from(spans)
+            })
+            .collect();
+        f.render_widget(Paragraph::new(lines), list_area);
+        let prompt = format!("> {}", self.input.text());
+        f.render_widget(Paragraph::new(prompt.as_str()), prompt_area);
+        f.set_cursor(prompt_area.x + 2 + self.input.cursor() as u16, prompt_area.y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shown(finder: &FinderView) -> Vec<&str> {
+        finder.matches.iter().map(|(i, _)| finder.paths[*i].1.as_str()).collect()
+    }
+
+    #[test]
+    fn batches_and_typing_keep_the_ranking() {
+        let root = Path::new("/r");
+        let paths = |names: &[&str]| names.iter().map(|x| root.join(x)).collect();
+        let mut finder = FinderView::new(root, true, true);
+        finder.handle_key(Key::Char('m'));
+        finder.add(paths(&["src/domain.rs", "LICENSE"]));
+        finder.add(paths(&["src/main.rs", "mod.rs"]));
+        assert_eq!(shown(&finder), vec!["mod.rs", "src/main.rs", "src/domain.rs"]);
+        finder.handle_key(Key::Char('a'));
+        assert_eq!(shown(&finder), vec!["src/main.rs", "src/domain.rs"]);
+        finder.handle_key(Key::Backspace);
+        finder.handle_key(Key::Backspace);
+        assert_eq!(shown(&finder).len(), 4);
+    }
+}
@@ -0,0 +1,241 @@
+use termion::event::Key;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph};
+use tui::Frame;
+
+use crate::zeuslib::actions::Category;
+use crate::zeuslib::config::Config;
+use crate::zeuslib::ui::dialog::LineInput;
+use crate::zeuslib::Backend;
+
+/// Width of the keys column; longer key lists are cut.
+const MAX_KEYS_WIDTH: usize = 30;
+
+/// One line of the help screen.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HelpLine {
+    Category(Category),
+    Action {
+        name: String,
+        keys: String,
+        description: &'static str,
+        modifies_fs: bool,
+    },
+}
+
+/// The keys bound to `name`, with the arguments of bindings that have any.
+fn action_keys(config: &Config, name: &str) -> String {
+    let mut keys: Vec<String> = config
+        .key_map
+        .iter()
+        .filter(|(_, binding)| binding.name == name)
+        .map(|(seq, binding)| {
+            if binding.args.is_empty() {
+                seq.to_string()
+            } else {
+                let command = binding.to_string();
+                format!("{} ({})", seq, command[binding.name.len()..].trim())
+            }
+        })
+        .collect();
+    keys.sort();
+    keys.join(", ")
+}
+
+/// Every action grouped by category, leaving out those that do not contain
+/// `query` in their name, keys or description.
+pub fn help_lines(config: &Config, query: &str) -> Vec<HelpLine> {
+    let query = query.to_lowercase();
+    let mut actions: Vec<(Category, &String, String, &'static str, bool)> = config
+        .actions
+        .iter()
+        .map(|(name, def)| {
+            let info = def.info;
+            (info.category, name, action_keys(config, name), info.description, info.modifies_fs)
+        })
+        .filter(|(_, name, keys, description, _)| {
+            query.is_empty()
+                || name.to_lowercase().contains(&query)
+                || keys.to_lowercase().contains(&query)
+                || description.to_lowercase().contains(&query)
+        })
+        .collect();
+    actions.sort();
+    let mut lines: Vec<HelpLine> = Vec::new();
+    let mut current: Option<Category> = None;
+    for (category, name, keys, description, modifies_fs) in actions {
+        if current != Some(category) {
+            current = Some(category);
+            lines.push(HelpLine::Category(category));
+        }
+        lines.push(HelpLine::Action {
+            name: name.clone(),
+            keys,
+            description,
+            modifies_fs,
+        });
+    }
+    lines
+}
+
+/// Full-screen list of every action with its keys and description. `/`
+/// narrows it down to the actions matching a search.
+#[derive(Clone, Default)]
+pub struct HelpView {
+    scroll: usize,
+    query: String,
+    /// The search being typed, if any.
+    search: Option<LineInput>,
+    /// Lines that fit on the screen when last drawn.
+    page: usize,
+}
+
+impl HelpView {
+    /// Handles a key. Returns `false` once the view should close.
+    pub fn handle_key(&mut self, k: Key) -> bool {
+        if let Some(search) = &mut self.search {
+            match k {
+                Key::Esc => {
+                    self.search = None;
+                    self.query.clear();
+                }
+                Key::Char('\n') => self.search = None,
+                k => {
+                    search.handle_key(k);
+                    self.query = search.text();
+                }
+            }
+            self.scroll = 0;
+            return true;
+        }
+        let page = self.page.max(1);
+        match k {
+            Key::Esc | Key::Char('q') | Key::Char('?') => return false,
+            Key::Char('/') => {
+                self.search = Some(LineInput::new(&self.query));
+            }
+            Key::Char('j') | Key::Down => self.scroll += 1,
+            Key::Char('k') | Key::Up => self.scroll = self.scroll.saturating_sub(1),
+            Key::PageDown | Key::Char(' ') | Key::Ctrl('f') => self.scroll += page,
+            Key::PageUp | Key::Ctrl('b') => self.scroll = self.scroll.saturating_sub(page),
+            Key::Ctrl('d') => self.scroll += page / 2,
+            Key::Ctrl('u') => self.scroll = self.scroll.saturating_sub(page / 2),
+            Key::Char('g') | Key::Home => self.scroll = 0,
+            Key::Char('G') | Key::End => self.scroll = usize::MAX,
+            _ => {}
+        }
+        true
+    }
+
+    fn footer(&self) -> String {
+        match &self.search {
+            Some(search) => format!("/{}", search.text()),
+            None if !self.query.is_empty() => {
+                format!("/{}  (/ to change, Esc in the search to clear)", self.query)
+            }
+            None => String::from("j/k scroll, / search, q close    * changes files"),
+        }
+    }
+
+    pub fn draw(&mut self, f: &mut Frame<Backend>, area: Rect, config: &Config) {
+        f.render_widget(Clear, area);
+        let lines = help_lines(config, &self.query);
+        let block = Block::default().borders(Borders::ALL).title("Help");
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        if inner.height < 2 {
+            return;
+        }
+        let list_area = Rect::new(inner.x, inner.y, inner.width, inner.height - 1);
+        let footer_area = Rect::new(inner.x, inner.y + inner.height - 1, inner.width, 1);
+
+        self.page = list_area.height as usize;
+        self.scroll = self.scroll.min(lines.len().saturating_sub(self.page));
+
+        let name_width = lines
+            .iter()
+            .map(|x| match x {
+                HelpLine::Action { name, .. } => name.len(),
+                HelpLine::Category(_) => 0,
+            })
+            .max()
+            .unwrap_or(0);
+        let keys_width = lines
+            .iter()
+            .map(|x| match x {
+                HelpLine::Action { keys, .. } => keys.chars().count(),
+                HelpLine::Category(_) => 0,
+            })
+            .max()
+            .unwrap_or(0)
+            .min(MAX_KEYS_WIDTH);
+        let spans: Vec<Spans> = lines
+            .iter()
+            .skip(self.scroll)
+            .take(self.page)
+            .map(|line| match line {
+                HelpLine::Category(category) => Spans::from(Span::styled(
+                    category.as_str(),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                HelpLine::Action {
+                    name,
+                    keys,
+                    description,
+                    modifies_fs,
+                } => {
+                    let keys: String = keys.chars().take(keys_width).collect();
+                    let name_style = if *modifies_fs {
+                        Style::default().fg(Color::Red)
+                    } else {
+                        Style::default()
+                    };
+                    Spans::from(vec![
+                        Span::raw(if *modifies_fs { " * " } else { "   " }),
+                        Span::styled(format!("{:w$}  ", name, w = name_width), name_style),
+                        Span::styled(
+                            format!("{:w$}  ", keys, w = keys_width),
+                            Style::default().fg(Color::Yellow),
+                        ),
+                        Span::raw(*description),
+                    ])
+                }
+            })
+            .collect();
+        f.render_widget(Paragraph::new(spans), list_area);
+        let footer = self.footer();
+        f.render_widget(Paragraph::new(footer.as_str()), footer_area);
+        if self.search.is_some() {
+            let cursor = self.search.as_ref().map(|x| x.cursor()).unwrap_or(0);
+            f.set_cursor(footer_area.x + 1 + cursor as u16, footer_area.y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_show_keys_and_filter() {
+        let config = Config::default();
+        let lines = help_lines(&config, "");
+        let trash = lines.iter().find_map(|x| match x {
+            HelpLine::Action {
+                name,
+                keys,
+                modifies_fs,
+                ..
+            } if name == "trash" => Some((keys.clone(), *modifies_fs)),
+            _ => None,
+        });
+        assert_eq!(trash, Some((String::from("dd"), true)));
+
+        let lines = help_lines(&config, "TRASH");
+        assert!(lines.contains(&HelpLine::Category(Category::Files)));
+        assert!(!lines.contains(&HelpLine::Category(Category::Tabs)));
+        assert!(help_lines(&config, "no such action").is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use std::iter::FromIterator;
+
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Color, Style};
+use tui::text::Span;
+use tui::widgets::{Block, Borders, Clear, List, ListItem, Widget};
+
+use crate::zeuslib::jobs::{Job, JobManager, JobStatus};
+use crate::zeuslib::utils::fs::FileSize;
+
+const BAR_WIDTH: usize = 20;
+
+/// One line of the jobs view.
+#[derive(Clone)]
+struct JobRow {
+    text: String,
+    selected: bool,
+}
+
+/// Overlay listing the running background jobs with their progress.
+#[derive(Clone)]
+pub struct JobsView {
+    rows: Vec<JobRow>,
+}
+
+fn progress_bar(ratio: f64) -> String {
+    let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+    let filled = filled.min(BAR_WIDTH);
+    format!(
+        "[{}{}]",
+        String::from_iter((0..filled).map(|_| '#')),
+        String::from_iter((filled..BAR_WIDTH).map(|_| '-'))
+    )
+}
+
+fn job_text(job: &Job) -> String {
+    let status = match job.status {
+        JobStatus::Running => "",
+        JobStatus::Paused => " (paused)",
+        JobStatus::Cancelling => " (cancelling)",
+    };
+    format!(
+        "{} {:>3.0}% {}/{} {}{}",
+        progress_bar(job.ratio()),
+        job.ratio() * 100.0,
+        FileSize::from_total_bytes(job.done_bytes),
+        FileSize::from_total_bytes(job.total_bytes),
+        job.description,
+        status
+    )
+}
+
+impl JobsView {
+    pub fn new(jobs: &JobManager) -> Self {
+        let rows = jobs
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(i, job)| JobRow {
+                text: job_text(job),
+                selected: i == jobs.selected,
+            })
+            .collect();
+        Self { rows }
+    }
+
+    /// Number of rows the view needs, borders included.
+    pub fn height(&self) -> u16 {
+        (self.rows.len().max(1) + 2) as u16
+    }
+}
+
+impl Widget for JobsView {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+        let block = Block::default().borders(Borders::ALL).title("Jobs");
+        let items: Vec<ListItem> = if self.rows.is_empty() {
+            vec![ListItem::new(Span::raw("No running jobs."))]
+        } else {
+            self.rows
+                .into_iter()
+                .map(|row| {
+                    let style = if row.selected {
+                        Style::default().fg(Color::Yellow)
+                    } else {
+                        Style::default().fg(Color::White)
+                    };
+                    ListItem::new(Span::raw(row.text)).style(style)
+                })
+                .collect()
+        };
+        List::new(items).block(block).render(area, buf);
+    }
+}
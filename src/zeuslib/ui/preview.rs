@@ -19,11 +19,7 @@ impl Preview {
         let file_contents: Option<String> = {
             if let Some(path) = &path {
                 if path.is_file() {
-                    if let Ok(s) = std::fs::read_to_string(&path) {
-                        Some(s)
-                    } else {
-                        None
-                    }
+                    std::fs::read_to_string(path).ok()
                 } else {
                     None
                 }
@@ -42,11 +38,7 @@ impl Preview {
         self.file_contents = {
             if let Some(path) = &path {
                 if path.is_file() {
-                    if let Ok(s) = std::fs::read_to_string(&path) {
-                        Some(s)
-                    } else {
-                        None
-                    }
+                    std::fs::read_to_string(path).ok()
                 } else {
                     None
                 }
@@ -0,0 +1,88 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph, Widget};
+
+use crate::zeuslib::config::Config;
+use crate::zeuslib::input::KeySequence;
+
+/// Overlay listing the bindings that continue the keys typed so far, laid
+/// out in as many columns as fit.
+#[derive(Clone)]
+pub struct WhichKeyView {
+    title: String,
+    /// The keys left to type and the command they run.
+    rows: Vec<(String, String)>,
+}
+
+impl WhichKeyView {
+    pub fn new(config: &Config, pending: &KeySequence) -> Self {
+        let rows = config
+            .bindings_with_prefix(pending)
+            .into_iter()
+            .map(|(keys, binding)| (keys.to_string(), binding.to_string()))
+            .collect();
+        Self {
+            title: pending.to_string(),
+            rows,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn key_width(&self) -> usize {
+        self.rows.iter().map(|x| x.0.chars().count()).max().unwrap_or(0)
+    }
+
+    fn column_width(&self) -> usize {
+        let command = self.rows.iter().map(|x| x.1.chars().count()).max().unwrap_or(0);
+        self.key_width() + 1 + command + 3
+    }
+
+    fn columns(&self, width: u16) -> usize {
+        let inner = (width as usize).saturating_sub(2);
+        (inner / self.column_width().max(1)).max(1)
+    }
+
+    fn lines(&self, columns: usize) -> usize {
+        self.rows.len().div_ceil(columns)
+    }
+
+    /// Number of rows the view needs at `width`, borders included.
+    pub fn height(&self, width: u16) -> u16 {
+        (self.lines(self.columns(width)) + 2) as u16
+    }
+}
+
+impl Widget for WhichKeyView {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+        let block = Block::default().borders(Borders::ALL).title(self.title.as_str());
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let columns = self.columns(area.width);
+        let per_column = self.lines(columns);
+        let key_width = self.key_width();
+        let column_width = self.column_width();
+        let key_style = Style::default().fg(Color::Yellow);
+        let lines: Vec<Spans> = (0..per_column)
+            .map(|line| {
+                let mut spans: Vec<Span> = Vec::new();
+                for column in 0..columns {
+                    if let Some((keys, command)) = self.rows.get(column * per_column + line) {
+                        let keys = format!("{:w$} ", keys, w = key_width);
+                        let pad = column_width - key_width - 1;
+                        spans.push(Span::styled(keys, key_style));
+                        spans.push(Span::raw(format!("{:w$}", command, w = pad)));
+                    }
+                }
+                Spans::from(spans)
+            })
+            .collect();
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
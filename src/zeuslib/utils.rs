@@ -1 +1,7 @@
 pub mod fs;
+pub mod glob;
+pub mod shellwords;
+pub mod matcher;
+pub mod fuzzy;
+#[cfg(test)]
+pub mod testing;
@@ -1,6 +1,15 @@
 pub mod filesize;
 pub mod paths;
+pub mod ops;
+pub mod trash;
+pub mod rename;
+pub mod mime;
+pub mod walk;
+pub mod info;
 
 pub use self::filesize::FileSize;
 pub use self::paths::CONFIG_DIR;
 pub use self::paths::CONFIG_FILE;
+pub use self::paths::LOCAL_CONFIG_FILE;
+pub use self::paths::{expand_tilde, private_temp_dir};
+pub use self::paths::{display_name, display_path};
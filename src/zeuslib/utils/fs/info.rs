@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::zeuslib::utils::fs::FileSize;
+
+/// Permissions in `ls -l` form, e.g. `drwxr-xr-x`, from a full `st_mode`
+/// that includes the file type.
+pub fn mode_string(mode: u32) -> String {
+    let kind = match mode & libc::S_IFMT {
+        libc::S_IFDIR => 'd',
+        libc::S_IFLNK => 'l',
+        libc::S_IFBLK => 'b',
+        libc::S_IFCHR => 'c',
+        libc::S_IFIFO => 'p',
+        libc::S_IFSOCK => 's',
+        _ => '-',
+    };
+    let mut s = String::new();
+    s.push(kind);
+    // Owner, group, others, each with the bit that replaces its `x`
+    let classes = [(6, 0o4000, 's', 'S'), (3, 0o2000, 's', 'S'), (0, 0o1000, 't', 'T')];
+    for (shift, special, set, unset) in classes {
+        let bits = (mode >> shift) & 0o7;
+        s.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+/// How long `recent_fs_space` reuses what it found for a directory.
+const SPACE_MAX_AGE: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    /// Names found by `user_name`, which is called on every frame drawn.
+    static ref USER_NAMES: Mutex<HashMap<u32, String>> = Mutex::new(HashMap::new());
+    /// Space found by `recent_fs_space` and when, by directory.
+    static ref SPACES: Mutex<HashMap<PathBuf, (Instant, Option<FsSpace>)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// The name of the user with id `uid`, or the id if it has none. Each id is
+/// looked up once.
+pub fn user_name(uid: u32) -> String {
+    let mut names = USER_NAMES.lock().unwrap();
+    names.entry(uid).or_insert_with(|| lookup_user_name(uid)).clone()
+}
+
+fn lookup_user_name(uid: u32) -> String {
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 1024];
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let r = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if r != 0 || result.is_null() {
+        return uid.to_string();
+    }
+    unsafe { CStr::from_ptr(pwd.pw_name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// `time` in local time, to the minute.
+pub fn format_time(time: SystemTime) -> String {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as libc::time_t,
+        Err(_) => 0,
+    };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::localtime_r(&secs, &mut tm);
+    }
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min
+    )
+}
+
+/// What a file list knows about an entry, read once when the list is
+/// refreshed so that drawing and sorting do not go back to the filesystem.
+///
+/// A symbolic link reports the type, size and mtime of its target like
+/// `Path::is_dir` and `Path::metadata` do, but its own mode and owner like
+/// `ls -l`. An entry that cannot be read at all keeps the defaults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileMeta {
+    pub is_dir: bool,
+    pub is_file: bool,
+    pub is_symlink: bool,
+    pub size: u64,
+    pub mtime: Option<SystemTime>,
+    /// The full `st_mode`, file type included.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub link_target: Option<PathBuf>,
+}
+
+impl FileMeta {
+    pub fn read(path: &Path) -> Self {
+        let own = match fs::symlink_metadata(path) {
+            Ok(own) => own,
+            Err(_) => return Self::default(),
+        };
+        let is_symlink = own.file_type().is_symlink();
+        let link_target = if is_symlink { fs::read_link(path).ok() } else { None };
+        // A dangling link is described by the link itself
+        let target = match is_symlink {
+            true => fs::metadata(path).unwrap_or_else(|_| own.clone()),
+            false => own.clone(),
+        };
+        Self {
+            is_dir: target.is_dir(),
+            is_file: target.is_file(),
+            is_symlink,
+            size: target.len(),
+            mtime: target.modified().ok(),
+            mode: own.mode(),
+            uid: own.uid(),
+            gid: own.gid(),
+            link_target,
+        }
+    }
+
+    /// Permissions, owner, size and modification time, and where a link
+    /// points.
+    pub fn describe(&self) -> String {
+        let mtime = self.mtime.map(format_time).unwrap_or_default();
+        let mut s = format!(
+            "{} {} {} {}",
+            mode_string(self.mode),
+            user_name(self.uid),
+            FileSize::from_total_bytes(self.size),
+            mtime
+        );
+        if let Some(target) = &self.link_target {
+            s.push_str(&format!(" -> {}", target.display()));
+        }
+        s
+    }
+}
+
+/// Space on a filesystem, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FsSpace {
+    /// What unprivileged users can still use.
+    pub free: u64,
+    pub total: u64,
+}
+
+/// Space on the filesystem holding `path`.
+pub fn fs_space(path: &Path) -> io::Result<FsSpace> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut st: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut st) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let block = st.f_frsize as u64;
+    Ok(FsSpace {
+        free: st.f_bavail as u64 * block,
+        total: st.f_blocks as u64 * block,
+    })
+}
+
+/// Like `fs_space`, but reuses what was found for `dir` in the last
+/// `SPACE_MAX_AGE`, for showing it on every frame.
+pub fn recent_fs_space(dir: &Path) -> Option<FsSpace> {
+    let now = Instant::now();
+    let mut spaces = SPACES.lock().unwrap();
+    if let Some((at, space)) = spaces.get(dir) {
+        if now.duration_since(*at) < SPACE_MAX_AGE {
+            return *space;
+        }
+    }
+    spaces.retain(|_, (at, _)| now.duration_since(*at) < SPACE_MAX_AGE);
+    let space = fs_space(dir).ok();
+    spaces.insert(dir.to_path_buf(), (now, space));
+    space
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_meta_and_space() {
+        assert_eq!(mode_string(0o040755), "drwxr-xr-x");
+        assert_eq!(mode_string(0o100644), "-rw-r--r--");
+        assert_eq!(mode_string(0o104754), "-rwsr-xr--");
+        assert_eq!(mode_string(0o041777), "drwxrwxrwt");
+        assert_eq!(mode_string(0o041776), "drwxrwxrwT");
+        assert_eq!(mode_string(0o120777), "lrwxrwxrwx");
+
+        let meta = FileMeta::read(Path::new("Cargo.toml"));
+        assert!(meta.is_file && !meta.is_dir && meta.size > 0);
+        assert_eq!(mode_string(meta.mode).chars().next(), Some('-'));
+        assert_eq!(FileMeta::read(Path::new("/no/such/file")), FileMeta::default());
+
+        let space = fs_space(Path::new("/")).unwrap();
+        assert!(space.total > 0 && space.free <= space.total);
+        assert!(fs_space(Path::new("/no/such/dir")).is_err());
+        assert_eq!(recent_fs_space(Path::new("/")).map(|x| x.total), Some(space.total));
+        assert_eq!(recent_fs_space(Path::new("/no/such/dir")), None);
+        assert_eq!(user_name(0), user_name(0));
+    }
+}
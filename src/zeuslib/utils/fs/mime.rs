@@ -0,0 +1,88 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+const SNIFF_LEN: usize = 512;
+
+// Magic numbers at the start of common file formats
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\x00", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"\x7fELF", "application/x-executable"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"ID3", "audio/mpeg"),
+    (b"\x1aE\xdf\xa3", "video/x-matroska"),
+    (b"SQLite format 3\x00", "application/vnd.sqlite3"),
+];
+
+/// Guesses the MIME type of `path` from its contents.
+///
+/// Directories are `inode/directory`. Files are recognized by a few well known
+/// magic numbers, otherwise they are `text/plain` if the first bytes look like
+/// UTF-8 text and `application/octet-stream` if not.
+pub fn sniff_mime(path: &Path) -> String {
+    if path.is_dir() {
+        return String::from("inode/directory");
+    }
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    if let Ok(f) = fs::File::open(path) {
+        let _ = f.take(SNIFF_LEN as u64).read_to_end(&mut buf);
+    }
+    if buf.is_empty() {
+        return String::from("text/plain");
+    }
+    for (magic, mime) in MAGIC {
+        if buf.starts_with(magic) {
+            return String::from(*mime);
+        }
+    }
+    if buf.len() >= 12 && &buf[4..8] == b"ftyp" {
+        return String::from("video/mp4");
+    }
+    if buf.len() >= 12 && &buf[0..4] == b"RIFF" {
+        match &buf[8..12] {
+            b"WAVE" => return String::from("audio/wav"),
+            b"WEBP" => return String::from("image/webp"),
+            b"AVI " => return String::from("video/x-msvideo"),
+            _ => {}
+        }
+    }
+    if looks_like_text(&buf) {
+        String::from("text/plain")
+    } else {
+        String::from("application/octet-stream")
+    }
+}
+
+fn looks_like_text(buf: &[u8]) -> bool {
+    if buf.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(buf) {
+        Ok(_) => true,
+        // The sample may have cut a multi-byte character in half
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// Matches a MIME type against a pattern like `image/png`, `image/*` or `*`.
+pub fn mime_matches(pattern: &str, mime: &str) -> bool {
+    if pattern == "*" || pattern == "*/*" {
+        return true;
+    }
+    match pattern.strip_suffix("/*") {
+        Some(top) => mime.split('/').next() == Some(top),
+        None => pattern == mime,
+    }
+}
@@ -0,0 +1,234 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+const COPY_CHUNK_SIZE: usize = 1 << 20;
+
+/// Callback receiving the number of bytes written since the previous call.
+pub type ProgressFn<'a> = dyn FnMut(u64) -> io::Result<()> + 'a;
+
+/// Returns a path inside `dir` named after `src` that does not exist yet.
+///
+/// If `dir` already contains an entry with the same name, a numeric suffix is
+/// appended to the file stem (`name_1.ext`, `name_2.ext`, ...) until a free name
+/// is found.
+pub fn unique_destination(src: &Path, dir: &Path) -> Option<PathBuf> {
+    let name = src.file_name()?;
+    let dst = dir.join(name);
+    if fs::symlink_metadata(&dst).is_err() {
+        return Some(dst);
+    }
+    let stem = Path::new(name).file_stem()?;
+    let ext = Path::new(name).extension();
+    let mut i: u32 = 1;
+    loop {
+        // Built from the original bytes, which need not be UTF-8
+        let mut name = OsString::from(stem);
+        name.push(format!("_{}", i));
+        if let Some(ext) = ext {
+            name.push(".");
+            name.push(ext);
+        }
+        let dst = dir.join(name);
+        if fs::symlink_metadata(&dst).is_err() {
+            return Some(dst);
+        }
+        i += 1;
+    }
+}
+
+fn check_not_inside(src: &Path, dst: &Path) -> io::Result<()> {
+    let src = src.canonicalize()?;
+    let dst_parent = dst
+        .parent()
+        .map(|p| p.canonicalize())
+        .transpose()?
+        .unwrap_or_default();
+    if dst_parent.starts_with(&src) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Cannot copy '{}' into itself", src.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Recursively copies `src` to `dst`.
+///
+/// Directories are created and their contents copied, symbolic links are
+/// recreated rather than followed, and regular files are copied with their
+/// permissions.
+pub fn copy_recursive(src: &Path, dst: &Path) -> io::Result<()> {
+    copy_recursive_with(src, dst, &mut |_| Ok(()))
+}
+
+/// Like `copy_recursive`, but calls `progress` with the number of bytes written
+/// after every chunk of file data.
+///
+/// If `progress` returns an error the copy stops, the partially written file is
+/// removed and the error is returned. The callback may also block, which pauses
+/// the copy.
+pub fn copy_recursive_with(src: &Path, dst: &Path, progress: &mut ProgressFn) -> io::Result<()> {
+    let meta = fs::symlink_metadata(src)?;
+    let ft = meta.file_type();
+    if ft.is_dir() {
+        check_not_inside(src, dst)?;
+        fs::create_dir(dst)?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_recursive_with(&entry.path(), &dst.join(entry.file_name()), progress)?;
+        }
+        fs::set_permissions(dst, meta.permissions())?;
+    } else if ft.is_symlink() {
+        let target = fs::read_link(src)?;
+        std::os::unix::fs::symlink(target, dst)?;
+    } else {
+        if let Err(e) = copy_file_with(src, dst, progress) {
+            let _ = fs::remove_file(dst);
+            return Err(e);
+        }
+        fs::set_permissions(dst, meta.permissions())?;
+    }
+    Ok(())
+}
+
+fn copy_file_with(src: &Path, dst: &Path, progress: &mut ProgressFn) -> io::Result<()> {
+    let mut reader = fs::File::open(src)?;
+    let mut writer = fs::OpenOptions::new().write(true).create_new(true).open(dst)?;
+    let mut buf = vec![0u8; COPY_CHUNK_SIZE];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n])?;
+        progress(n as u64)?;
+    }
+    Ok(())
+}
+
+/// Total size in bytes of the regular files below `path`.
+pub fn total_size(path: &Path) -> u64 {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(_) => return 0,
+    };
+    if meta.is_dir() {
+        fs::read_dir(path)
+            .map(|dir| dir.flatten().map(|e| total_size(&e.path())).sum())
+            .unwrap_or(0)
+    } else if meta.file_type().is_symlink() {
+        0
+    } else {
+        meta.len()
+    }
+}
+
+/// Recursively removes `path`, whether it is a file, link or directory.
+pub fn remove_recursive(path: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.file_type().is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Moves `src` to `dst`.
+///
+/// A plain rename is tried first. When that fails because the destination is
+/// on another filesystem, the tree is copied and the source removed afterwards.
+pub fn move_path(src: &Path, dst: &Path) -> io::Result<()> {
+    move_path_with(src, dst, &mut |_| Ok(()))
+}
+
+/// Like `move_path`, reporting copied bytes to `progress` when the move has to
+/// fall back to copying. A rename is not reported: once it has happened the
+/// move is done and can no longer be cancelled.
+pub fn move_path_with(src: &Path, dst: &Path, progress: &mut ProgressFn) -> io::Result<()> {
+    move_path_using(src, dst, progress, &|src, dst| fs::rename(src, dst))
+}
+
+/// `move_path_with`, trying `rename` before falling back to copying.
+fn move_path_using(
+    src: &Path,
+    dst: &Path,
+    progress: &mut ProgressFn,
+    rename: &dyn Fn(&Path, &Path) -> io::Result<()>,
+) -> io::Result<()> {
+    if fs::symlink_metadata(src)?.is_dir() {
+        check_not_inside(src, dst)?;
+    }
+    match rename(src, dst) {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            if let Err(e) = copy_recursive_with(src, dst, progress) {
+                let _ = remove_recursive(dst);
+                return Err(e);
+            }
+            remove_recursive(src)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zeuslib::utils::testing::TempDir;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn unique_destinations() {
+        let tmp = TempDir::new("unique");
+        tmp.touch(&["src/a.txt", "dst/a.txt", "dst/a_1.txt", "src/b"]);
+        let (src, dst) = (tmp.path().join("src"), tmp.path().join("dst"));
+        assert_eq!(unique_destination(&src.join("a.txt"), &dst), Some(dst.join("a_2.txt")));
+        assert_eq!(unique_destination(&src.join("b"), &dst), Some(dst.join("b")));
+        let latin1 = |s: &[u8]| PathBuf::from(OsStr::from_bytes(s));
+        fs::write(dst.join(latin1(b"caf\xe9.txt")), "").unwrap();
+        assert_eq!(
+            unique_destination(&src.join(latin1(b"caf\xe9.txt")), &dst),
+            Some(dst.join(latin1(b"caf\xe9_1.txt")))
+        );
+    }
+
+    #[test]
+    fn copying_a_directory_into_itself_fails() {
+        let tmp = TempDir::new("copy-self");
+        tmp.touch(&["dir/file"]);
+        let dir = tmp.path().join("dir");
+        assert!(copy_recursive(&dir, &dir.join("copy")).is_err());
+        assert!(move_path(&dir, &dir.join("moved")).is_err());
+        assert!(!dir.join("copy").exists() && dir.join("file").is_file());
+    }
+
+    #[test]
+    fn moves_across_filesystems_copy_and_remove() {
+        let tmp = TempDir::new("exdev");
+        tmp.touch(&["src/sub/file"]);
+        fs::write(tmp.path().join("src/sub/file"), "data").unwrap();
+        let (src, dst) = (tmp.path().join("src"), tmp.path().join("dst"));
+        let exdev = |_: &Path, _: &Path| Err(io::Error::from_raw_os_error(libc::EXDEV));
+        let mut copied = 0;
+        let mut progress = |n: u64| {
+            copied += n;
+            Ok(())
+        };
+        move_path_using(&src, &dst, &mut progress, &exdev).unwrap();
+        assert_eq!(copied, 4);
+        assert!(!src.exists());
+        assert_eq!(fs::read_to_string(dst.join("sub/file")).unwrap(), "data");
+
+        // A cancelled fallback leaves the source as it was
+        let cancel = &mut |_| Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        assert!(move_path_using(&dst, &src, cancel, &exdev).is_err());
+        assert!(!src.exists() && dst.join("sub/file").is_file());
+
+        // A rename that went through is a success whatever progress says
+        move_path_with(&dst, &src, cancel).unwrap();
+        assert!(src.join("sub/file").is_file());
+    }
+}
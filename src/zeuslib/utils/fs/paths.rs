@@ -1,21 +1,101 @@
-use directories::ProjectDirs;
-use std::path::{PathBuf};
+use directories::{BaseDirs, ProjectDirs};
+use std::ffi::{CString, OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+
+/// Name of the project-local config file, loaded from the starting
+/// directory after the user's config file. It cannot define openers.
+pub const LOCAL_CONFIG_FILE: &str = ".zeus.toml";
 
 lazy_static! {
     pub static ref CONFIG_DIR: Option<PathBuf> = {
-        if let Some(proj_dirs) = ProjectDirs::from("com", "",  "ZeusFm") {
-            Some(proj_dirs.config_dir().to_path_buf())
-        } else {
-            None
-        }
+        ProjectDirs::from("com", "",  "ZeusFm").map(|proj_dirs| proj_dirs.config_dir().to_path_buf())
     };
 
 
     pub static ref CONFIG_FILE: Option<PathBuf> = {
-        if let Some(dir) = &*CONFIG_DIR {
-            Some(dir.join("zeus.toml"))
+        (*CONFIG_DIR).as_ref().map(|dir| dir.join("zeus.toml"))
+    };
+
+    /// The home trash, `$XDG_DATA_HOME/Trash`
+    pub static ref TRASH_DIR: Option<PathBuf> = {
+        BaseDirs::new().map(|dirs| dirs.data_dir().join("Trash"))
+    };
+}
+
+/// Expands a leading `~` in `path` to the user's home directory.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    if path == "~" || path.starts_with("~/") {
+        if let Some(dirs) = BaseDirs::new() {
+            return dirs.home_dir().join(path[1..].trim_start_matches('/'));
+        }
+    }
+    PathBuf::from(path)
+}
+
+/// Creates a new directory that only the user can enter, in
+/// `$XDG_RUNTIME_DIR` or else the system's temporary directory, named
+/// `prefix` followed by random characters.
+pub fn private_temp_dir(prefix: &str) -> io::Result<PathBuf> {
+    let base = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(std::env::temp_dir);
+    let template = base.join(format!("{}XXXXXX", prefix));
+    let template = CString::new(template.into_os_string().into_vec())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut bytes = template.into_bytes_with_nul();
+    // mkdtemp creates the directory with mode 0700
+    if unsafe { libc::mkdtemp(bytes.as_mut_ptr() as *mut libc::c_char) }.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    bytes.pop();
+    Ok(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+/// `name` as shown on screen: bytes that are not UTF-8 become U+FFFD and
+/// control characters are escaped, so that no name can break the layout.
+pub fn display_name(name: &OsStr) -> String {
+    let mut s = String::new();
+    for c in name.to_string_lossy().chars() {
+        if c.is_control() {
+            s.extend(c.escape_default());
         } else {
-            None
+            s.push(c);
         }
-    };
+    }
+    s
+}
+
+/// `path` as shown on screen, like `display_name`.
+pub fn display_path(path: &Path) -> String {
+    display_name(path.as_os_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn private_temp_dirs() {
+        use std::os::unix::fs::PermissionsExt;
+        let a = private_temp_dir("zeus-test-").unwrap();
+        let b = private_temp_dir("zeus-test-").unwrap();
+        assert_ne!(a, b);
+        let mode = std::fs::metadata(&a).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        std::fs::remove_dir(a).unwrap();
+        std::fs::remove_dir(b).unwrap();
+    }
+
+    #[test]
+    fn names_are_made_printable() {
+        assert_eq!(display_name(OsStr::new("plain.txt")), "plain.txt");
+        assert_eq!(display_name(OsStr::new("a\nb\tc\x1b")), "a\\nb\\tc\\u{1b}");
+        assert_eq!(display_name(OsStr::new("\u{85}")), "\\u{85}");
+        // Latin-1 "caf\xe9"
+        assert_eq!(display_name(OsStr::from_bytes(b"caf\xe9")), "caf\u{fffd}");
+    }
 }
@@ -0,0 +1,279 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// A validated set of renames inside one directory.
+#[derive(Debug)]
+pub struct RenamePlan {
+    pub dir: PathBuf,
+    /// `(old name, new name)` pairs, unchanged names left out.
+    pub renames: Vec<(String, String)>,
+    /// Whether some names are swapped or chained, so the renames have to go
+    /// through temporary names.
+    pub has_cycle: bool,
+}
+
+/// Checks a bulk rename of `old` names to `new` names in `dir`.
+///
+/// Fails if the number of names changed, a new name is empty or contains a
+/// `/`, two entries would get the same name, or a new name is already taken by
+/// an entry that is not itself being renamed.
+pub fn plan_renames(dir: &Path, old: &[String], new: &[String]) -> Result<RenamePlan, String> {
+    if old.len() != new.len() {
+        return Err(format!(
+            "Expected {} names but found {}",
+            old.len(),
+            new.len()
+        ));
+    }
+    let mut renames: Vec<(String, String)> = Vec::new();
+    for (o, n) in old.iter().zip(new.iter()) {
+        if n.is_empty() {
+            return Err(format!("Empty name for '{}'", o));
+        }
+        if n.contains('/') || n == "." || n == ".." {
+            return Err(format!("Invalid name '{}'", n));
+        }
+        if o != n {
+            renames.push((o.clone(), n.clone()));
+        }
+    }
+
+    let mut targets: HashSet<&str> = HashSet::new();
+    for (_, n) in renames.iter() {
+        if !targets.insert(n.as_str()) {
+            return Err(format!("Several entries would be named '{}'", n));
+        }
+    }
+    let sources: HashSet<&str> = renames.iter().map(|(o, _)| o.as_str()).collect();
+    let untouched: HashSet<&str> = old
+        .iter()
+        .map(|x| x.as_str())
+        .filter(|x| !sources.contains(x))
+        .collect();
+    for (_, n) in renames.iter() {
+        let taken = fs::symlink_metadata(dir.join(n)).is_ok() && !sources.contains(n.as_str());
+        if taken || untouched.contains(n.as_str()) {
+            return Err(format!("'{}' already exists", n));
+        }
+    }
+
+    let has_cycle = find_cycle(&renames);
+    Ok(RenamePlan {
+        dir: dir.to_path_buf(),
+        renames,
+        has_cycle,
+    })
+}
+
+/// Returns `true` if following old -> new names ever leads back to a start.
+fn find_cycle(renames: &[(String, String)]) -> bool {
+    let next: HashMap<&str, &str> = renames
+        .iter()
+        .map(|(o, n)| (o.as_str(), n.as_str()))
+        .collect();
+    for (start, _) in renames.iter() {
+        let mut cur = start.as_str();
+        let mut steps = 0;
+        while let Some(n) = next.get(cur) {
+            if *n == start {
+                return true;
+            }
+            cur = n;
+            steps += 1;
+            if steps > renames.len() {
+                break;
+            }
+        }
+    }
+    false
+}
+
+impl RenamePlan {
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    /// One line per rename, for showing the plan before applying it.
+    pub fn preview(&self) -> Vec<String> {
+        self.renames
+            .iter()
+            .map(|(o, n)| format!("{} -> {}", o, n))
+            .collect()
+    }
+
+    /// Performs the renames.
+    ///
+    /// Every entry is first moved to a temporary name, so swaps and chains
+    /// never overwrite one another. No rename replaces an entry, so one that
+    /// turned up at a new name since the plan was made fails it. If a rename
+    /// fails, the entries renamed so far get their old names back.
+    pub fn apply(&self) -> io::Result<()> {
+        let pid = std::process::id();
+        let mut staged: Vec<Staged> = Vec::new();
+        for (i, (o, n)) in self.renames.iter().enumerate() {
+            let old = self.dir.join(o);
+            let tmp = self.dir.join(format!(".zeus-rename-{}-{}", pid, i));
+            if let Err(e) = rename_no_replace(&old, &tmp) {
+                return Err(roll_back(e, &staged, 0));
+            }
+            staged.push(Staged {
+                old,
+                tmp,
+                new: self.dir.join(n),
+            });
+        }
+        for (done, entry) in staged.iter().enumerate() {
+            if let Err(e) = rename_no_replace(&entry.tmp, &entry.new) {
+                return Err(roll_back(e, &staged, done));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renames `from` to `to`, failing if `to` exists rather than replacing it.
+fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+    let c_path = |p: &Path| {
+        CString::new(p.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    };
+    let (c_from, c_to) = (c_path(from)?, c_path(to)?);
+    let r = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            c_from.as_ptr(),
+            libc::AT_FDCWD,
+            c_to.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if r == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        // The kernel or filesystem cannot do it; check just before instead
+        Some(libc::ENOSYS) | Some(libc::EINVAL) => {
+            if fs::symlink_metadata(to).is_ok() {
+                return Err(io::Error::from_raw_os_error(libc::EEXIST));
+            }
+            fs::rename(from, to)
+        }
+        _ => Err(err),
+    }
+}
+
+/// An entry of a plan being applied, moved aside under `tmp`.
+struct Staged {
+    old: PathBuf,
+    tmp: PathBuf,
+    new: PathBuf,
+}
+
+/// Undoes a plan that failed with `err` after the first `done` entries of
+/// `staged` got their new names: those go back to their temporary names,
+/// then all of them to their old ones. Returns `err`, adding where entries
+/// that could not be restored were left.
+fn roll_back(err: io::Error, staged: &[Staged], done: usize) -> io::Error {
+    let name = |p: &Path| p.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let mut lost: Vec<String> = Vec::new();
+    let mut restorable: Vec<&Staged> = Vec::new();
+    for (i, entry) in staged.iter().enumerate() {
+        if i < done && rename_no_replace(&entry.new, &entry.tmp).is_err() {
+            lost.push(format!("'{}' (left as '{}')", name(&entry.old), name(&entry.new)));
+        } else {
+            restorable.push(entry);
+        }
+    }
+    for entry in restorable {
+        if rename_no_replace(&entry.tmp, &entry.old).is_err() {
+            lost.push(format!("'{}' (left as '{}')", name(&entry.old), name(&entry.tmp)));
+        }
+    }
+    if lost.is_empty() {
+        return err;
+    }
+    io::Error::new(err.kind(), format!("{}; could not restore {}", err, lost.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zeuslib::utils::testing::TempDir;
+
+    fn names(v: &[&str]) -> Vec<String> {
+        v.iter().map(|x| String::from(*x)).collect()
+    }
+
+    #[test]
+    fn plan_detects_problems() {
+        let dir = Path::new("/nonexistent-zeus-test-dir");
+        let old = names(&["a", "b", "c"]);
+        assert!(plan_renames(dir, &old, &names(&["a", "b"])).is_err());
+        assert!(plan_renames(dir, &old, &names(&["x", "x", "c"])).is_err());
+        assert!(plan_renames(dir, &old, &names(&["c", "b", "d"])).is_ok());
+        assert!(plan_renames(dir, &old, &names(&["b", "b", "c"])).is_err());
+        assert!(plan_renames(dir, &old, &names(&["a/b", "b", "c"])).is_err());
+    }
+
+    #[test]
+    fn plan_detects_cycles() {
+        let dir = Path::new("/nonexistent-zeus-test-dir");
+        let old = names(&["a", "b", "c"]);
+        let plan = plan_renames(dir, &old, &names(&["b", "a", "c"])).unwrap();
+        assert!(plan.has_cycle);
+        assert_eq!(plan.renames.len(), 2);
+        let plan = plan_renames(dir, &old, &names(&["a", "c", "d"])).unwrap();
+        assert!(!plan.has_cycle);
+    }
+
+    #[test]
+    fn failed_plans_are_rolled_back() {
+        let tmp = TempDir::new("rename");
+        let dir = tmp.path();
+        for name in &["a", "b", "c"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        let contents = |name: &str| fs::read_to_string(dir.join(name)).ok();
+        let listing = || {
+            let mut names: Vec<String> = fs::read_dir(dir)
+                .unwrap()
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        let old = names(&["a", "b", "c"]);
+
+        // `c` disappears before it can be moved aside
+        let plan = plan_renames(dir, &old, &names(&["b", "a", "d"])).unwrap();
+        fs::rename(dir.join("c"), dir.join("e")).unwrap();
+        assert!(plan.apply().is_err());
+        assert_eq!(listing(), vec!["a", "b", "e"]);
+        assert_eq!(contents("a").as_deref(), Some("a"));
+        fs::rename(dir.join("e"), dir.join("c")).unwrap();
+
+        // `d` turns up as a directory after the swap has been done
+        let plan = plan_renames(dir, &old, &names(&["b", "a", "d"])).unwrap();
+        fs::create_dir(dir.join("d")).unwrap();
+        assert!(plan.apply().is_err());
+        assert_eq!(listing(), vec!["a", "b", "c", "d"]);
+        assert_eq!(contents("a").as_deref(), Some("a"));
+        assert_eq!(contents("b").as_deref(), Some("b"));
+        fs::remove_dir(dir.join("d")).unwrap();
+
+        // A file that turns up at a new name is not replaced
+        let plan = plan_renames(dir, &old, &names(&["b", "a", "d"])).unwrap();
+        fs::write(dir.join("d"), "new").unwrap();
+        assert!(plan.apply().is_err());
+        assert_eq!(listing(), vec!["a", "b", "c", "d"]);
+        assert_eq!(contents("c").as_deref(), Some("c"));
+        assert_eq!(contents("d").as_deref(), Some("new"));
+    }
+}
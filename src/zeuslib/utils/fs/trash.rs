@@ -0,0 +1,254 @@
+// Home trash following the freedesktop.org Trash specification.
+//
+// Trashed items are moved into `$XDG_DATA_HOME/Trash/files`, and for each of
+// them a `.trashinfo` file recording the original location and the deletion
+// time is written to `$XDG_DATA_HOME/Trash/info`.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::zeuslib::utils::fs::ops::{move_path_with, ProgressFn};
+use crate::zeuslib::utils::fs::paths::TRASH_DIR;
+
+const INFO_EXT: &str = "trashinfo";
+
+/// Contents of a `.trashinfo` file.
+#[derive(Debug, Clone)]
+pub struct TrashInfo {
+    pub path: PathBuf,
+    pub deletion_date: String,
+}
+
+impl TrashInfo {
+    fn parse(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines();
+        if lines.next()?.trim() != "[Trash Info]" {
+            return None;
+        }
+        let mut path = None;
+        let mut deletion_date = String::new();
+        for line in lines {
+            if let Some(v) = line.strip_prefix("Path=") {
+                path = Some(PathBuf::from(OsStr::from_bytes(&percent_decode(v.trim()))));
+            } else if let Some(v) = line.strip_prefix("DeletionDate=") {
+                deletion_date = String::from(v.trim());
+            }
+        }
+        Some(Self {
+            path: path?,
+            deletion_date,
+        })
+    }
+
+    fn to_file_contents(&self) -> String {
+        format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            percent_encode(self.path.as_os_str().as_bytes()),
+            self.deletion_date
+        )
+    }
+}
+
+fn trash_dir() -> io::Result<&'static PathBuf> {
+    (*TRASH_DIR)
+        .as_ref()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No trash directory"))
+}
+
+/// Directory holding the trashed files themselves.
+pub fn trash_files_dir() -> Option<PathBuf> {
+    (*TRASH_DIR).as_ref().map(|d| d.join("files"))
+}
+
+fn info_path_for(trashed: &Path) -> io::Result<PathBuf> {
+    let name = trashed
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid trash entry"))?;
+    let mut info_name = name.to_os_string();
+    info_name.push(".");
+    info_name.push(INFO_EXT);
+    Ok(trash_dir()?.join("info").join(info_name))
+}
+
+/// Returns `true` if `path` is an entry of the trash `files` directory.
+pub fn is_in_trash(path: &Path) -> bool {
+    match (path.parent(), trash_files_dir()) {
+        (Some(parent), Some(files)) => parent == files,
+        _ => false,
+    }
+}
+
+/// Reads the `.trashinfo` belonging to an entry of the trash `files` directory.
+pub fn read_info(trashed: &Path) -> io::Result<TrashInfo> {
+    let contents = fs::read_to_string(info_path_for(trashed)?)?;
+    TrashInfo::parse(&contents)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed trash info"))
+}
+
+/// Moves `path` into the trash and returns its new location, reporting
+/// progress as `move_path_with` does when the trash is on another
+/// filesystem. An entry of the trash itself cannot be trashed again, as that
+/// would leave its info file behind.
+pub fn trash_path(path: &Path, progress: &mut ProgressFn) -> io::Result<PathBuf> {
+    if is_in_trash(path) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Already in the trash; delete or restore it instead",
+        ));
+    }
+    let dir = trash_dir()?;
+    let files_dir = dir.join("files");
+    let info_dir = dir.join("info");
+    fs::create_dir_all(&files_dir)?;
+    fs::create_dir_all(&info_dir)?;
+
+    let original = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+    let name = original
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Cannot trash this path"))?
+        .to_os_string();
+
+    let info = TrashInfo {
+        path: original.clone(),
+        deletion_date: local_timestamp(),
+    };
+
+    // The info file is created exclusively first; whoever creates it owns the
+    // name, as required by the specification.
+    let mut i: u32 = 0;
+    loop {
+        let mut candidate = name.clone();
+        if i > 0 {
+            candidate.push(format!("_{}", i));
+        }
+        let trashed = files_dir.join(&candidate);
+        let info_path = info_path_for(&trashed)?;
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(mut f) => {
+                if fs::symlink_metadata(&trashed).is_ok() {
+                    // Stale entry without info file; leave it alone
+                    drop(f);
+                    fs::remove_file(&info_path)?;
+                    i += 1;
+                    continue;
+                }
+                f.write_all(info.to_file_contents().as_bytes())?;
+                drop(f);
+                if let Err(e) = move_path_with(&original, &trashed, progress) {
+                    let _ = fs::remove_file(&info_path);
+                    return Err(e);
+                }
+                return Ok(trashed);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => i += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Moves an entry of the trash back to where it was trashed from.
+///
+/// Fails if something else now exists at the original location.
+pub fn restore(trashed: &Path, progress: &mut ProgressFn) -> io::Result<PathBuf> {
+    let info = read_info(trashed)?;
+    if fs::symlink_metadata(&info.path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("'{}' already exists", info.path.display()),
+        ));
+    }
+    if let Some(parent) = info.path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_path_with(trashed, &info.path, progress)?;
+    fs::remove_file(info_path_for(trashed)?)?;
+    Ok(info.path)
+}
+
+fn local_timestamp() -> String {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        libc::localtime_r(&now, &mut tm);
+    }
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+fn percent_encode(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            s.push(b as char);
+        } else {
+            s.push_str(&format!("%{:02X}", b));
+        }
+    }
+    s
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                out.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trashinfo_roundtrip() {
+        let info = TrashInfo {
+            path: PathBuf::from(OsStr::from_bytes(b"/home/u/r\xe9sum\xe9 50%.txt")),
+            deletion_date: String::from("2020-08-01T12:30:00"),
+        };
+        let contents = info.to_file_contents();
+        assert!(contents.contains("Path=/home/u/r%E9sum%E9%2050%25.txt"));
+        let parsed = TrashInfo::parse(&contents).unwrap();
+        assert_eq!(parsed.path, info.path);
+        assert_eq!(parsed.deletion_date, info.deletion_date);
+    }
+
+    #[test]
+    fn trash_entries_are_not_trashed_again() {
+        if let Some(files) = trash_files_dir() {
+            let err = trash_path(&files.join("a.txt"), &mut |_| Ok(())).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::zeuslib::utils::glob::Glob;
+
+/// One pattern read from a `.gitignore`.
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    /// The directory holding the `.gitignore`, which anchored patterns are
+    /// relative to.
+    base: PathBuf,
+    glob: Glob,
+    /// The pattern contains a `/`, so it is matched against the path below
+    /// `base` rather than against the name alone.
+    anchored: bool,
+    /// The pattern ends with `/` and only applies to directories.
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    fn parse(line: &str, base: &Path) -> Option<Self> {
+        let line = line.trim_end();
+        // Negations would need the rules to be ordered; they are left out
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            return None;
+        }
+        let (line, dir_only) = match line.strip_suffix('/') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let anchored = line.contains('/');
+        let pattern = line.trim_start_matches('/');
+        let glob = Glob::new(pattern).ok()?;
+        Some(Self {
+            base: base.to_path_buf(),
+            glob,
+            anchored,
+            dir_only,
+        })
+    }
+
+    fn is_match(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            match path.strip_prefix(&self.base) {
+                Ok(rel) => self.glob.is_match(&rel.to_string_lossy()),
+                Err(_) => false,
+            }
+        } else {
+            let name = path.file_name().unwrap_or_default();
+            self.glob.is_match(&name.to_string_lossy())
+        }
+    }
+}
+
+/// What a walk leaves out.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Names that are neither listed nor entered.
+    pub ignore: Vec<Glob>,
+    /// Also leave out what the `.gitignore` files along the way list.
+    pub git_ignore: bool,
+}
+
+/// Lists every entry below `root`, depth first, passing each to `found`.
+/// Symbolic links to directories are listed but not followed. The walk
+/// stops early once `found` returns `false`.
+pub fn walk(root: &Path, options: &WalkOptions, found: &mut dyn FnMut(PathBuf) -> bool) {
+    // Directories still to read, with the `.gitignore` rules above them
+    let mut stack: Vec<(PathBuf, Vec<IgnoreRule>)> = vec![(root.to_path_buf(), Vec::new())];
+    while let Some((dir, mut rules)) = stack.pop() {
+        if options.git_ignore {
+            if let Ok(text) = fs::read_to_string(dir.join(".gitignore")) {
+                rules.extend(text.lines().filter_map(|x| IgnoreRule::parse(x, &dir)));
+            }
+        }
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let mut subdirs: Vec<PathBuf> = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if options.ignore.iter().any(|g| g.is_match(&name)) {
+                continue;
+            }
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if rules.iter().any(|r| r.is_match(&path, is_dir)) {
+                continue;
+            }
+            if is_dir {
+                subdirs.push(path.clone());
+            }
+            if !found(path) {
+                return;
+            }
+        }
+        // Reversed so that the stack reads them in directory order
+        for subdir in subdirs.into_iter().rev() {
+            stack.push((subdir, rules.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zeuslib::utils::testing::TempDir;
+
+    #[test]
+    fn walk_skips_ignored() {
+        let tmp = TempDir::new("walk");
+        let root = tmp.path();
+        fs::create_dir(root.join("build")).unwrap();
+        tmp.touch(&["src/main.rs", "src/ui/list.rs", "target/debug/app", "docs/a.log"]);
+        fs::write(root.join(".gitignore"), "# build output\nbuild/\n*.log\n/src/ui\n").unwrap();
+
+        let list = |options: &WalkOptions| {
+            let mut paths: Vec<String> = Vec::new();
+            walk(root, options, &mut |p| {
+                paths.push(p.strip_prefix(root).unwrap().to_string_lossy().into_owned());
+                true
+            });
+            paths.sort();
+            paths
+        };
+        let options = WalkOptions {
+            ignore: vec![Glob::new("target").unwrap()],
+            git_ignore: true,
+        };
+        assert_eq!(list(&options), vec![".gitignore", "docs", "src", "src/main.rs"]);
+        let options = WalkOptions::default();
+        assert_eq!(list(&options).len(), 11);
+    }
+}
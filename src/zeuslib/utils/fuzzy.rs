@@ -0,0 +1,140 @@
+/// Points for each character of the pattern found in the text.
+const SCORE_MATCH: i64 = 16;
+/// Extra points for a character right after the previous matched one.
+const BONUS_CONSECUTIVE: i64 = 8;
+/// Extra points for a character starting a word, e.g. after `_` or `.`.
+const BONUS_WORD_START: i64 = 8;
+/// Extra points for a character starting a path component.
+const BONUS_COMPONENT_START: i64 = 12;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP: i64 = 1;
+
+/// A fuzzy match: how good it is and which characters matched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// Indices of the matched characters (not bytes) in the text.
+    pub positions: Vec<usize>,
+}
+
+fn bonus(prev: Option<char>, c: char) -> i64 {
+    match prev {
+        None | Some('/') => BONUS_COMPONENT_START,
+        Some(p) if !p.is_alphanumeric() => BONUS_WORD_START,
+        Some(p) if p.is_lowercase() && c.is_uppercase() => BONUS_WORD_START,
+        _ => 0,
+    }
+}
+
+/// The matched positions of the shortest match of `pattern` that starts at
+/// or after `start`: the characters are found as early as possible, then the
+/// window is shrunk from its end.
+fn match_from(
+    pattern: &[char],
+    text: &[char],
+    start: usize,
+    eq: &dyn Fn(char, char) -> bool,
+) -> Option<Vec<usize>> {
+    let mut pi = 0;
+    let mut end = None;
+    for (ti, &t) in text.iter().enumerate().skip(start) {
+        if eq(pattern[pi], t) {
+            pi += 1;
+            if pi == pattern.len() {
+                end = Some(ti);
+                break;
+            }
+        }
+    }
+    let mut positions = vec![0; pattern.len()];
+    let mut ti = end? + 1;
+    for pi in (0..pattern.len()).rev() {
+        ti -= 1;
+        while !eq(pattern[pi], text[ti]) {
+            ti -= 1;
+        }
+        positions[pi] = ti;
+    }
+    Some(positions)
+}
+
+fn score(text: &[char], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    for (i, &pos) in positions.iter().enumerate() {
+        score += SCORE_MATCH;
+        let prev = if pos == 0 { None } else { Some(text[pos - 1]) };
+        score += bonus(prev, text[pos]);
+        if i > 0 {
+            let gap = (pos - positions[i - 1] - 1) as i64;
+            if gap == 0 {
+                score += BONUS_CONSECUTIVE;
+            } else {
+                score -= PENALTY_GAP_START + gap * PENALTY_GAP;
+            }
+        }
+    }
+    score
+}
+
+/// Matches `pattern` against `text` if all its characters appear in order.
+/// Lower-case pattern characters also match upper-case ones unless
+/// `ignore_case` is unset.
+///
+/// Every place the first character occurs is tried as a start, and the best
+/// scoring match wins, so `cfg` matches `cfgfile` rather than `config`.
+pub fn fuzzy_match(pattern: &str, text: &str, ignore_case: bool) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    let eq = |p: char, t: char| {
+        if ignore_case {
+            p.to_lowercase().eq(t.to_lowercase())
+        } else {
+            p == t
+        }
+    };
+    let mut best: Option<FuzzyMatch> = None;
+    for start in 0..text.len() {
+        if !eq(pattern[0], text[start]) {
+            continue;
+        }
+        // No match from here means none from any later start either
+        let positions = match match_from(&pattern, &text, start, &eq) {
+            Some(positions) => positions,
+            None => break,
+        };
+        let score = score(&text, &positions);
+        let better = match &best {
+            None => true,
+            Some(b) => score > b.score,
+        };
+        if better {
+            best = Some(FuzzyMatch { score, positions });
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_and_positions() {
+        assert_eq!(fuzzy_match("abc", "xaxbxc", true).unwrap().positions, vec![1, 3, 5]);
+        assert_eq!(fuzzy_match("ab", "a_xab", true).unwrap().positions, vec![3, 4]);
+        assert!(fuzzy_match("abd", "abc", true).is_none());
+        assert!(fuzzy_match("A", "a", false).is_none());
+        assert!(fuzzy_match("a", "A", true).is_some());
+
+        let score = |p, t| fuzzy_match(p, t, true).unwrap().score;
+        assert!(score("main", "src/main.rs") > score("main", "src/domain.rs"));
+        assert!(score("fb", "foobar") < score("fb", "foo_bar"));
+        assert!(score("cfg", "config/cfgfile.rs") > score("cfg", "config/options.rs"));
+    }
+}
@@ -0,0 +1,101 @@
+use regex::{Regex, RegexBuilder};
+
+/// A shell-style wildcard pattern (`*`, `?`, `[...]`) matched against file
+/// names.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    re: Regex,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Self::with_case(pattern, true)
+    }
+
+    pub fn with_case(pattern: &str, case_sensitive: bool) -> Result<Self, regex::Error> {
+        let re = RegexBuilder::new(&glob_to_regex(pattern))
+            .case_insensitive(!case_sensitive)
+            .build()?;
+        Ok(Self {
+            pattern: String::from(pattern),
+            re,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.re.is_match(name)
+    }
+}
+
+/// Translates a glob into an anchored regular expression.
+///
+/// `*` matches any run of characters except `/`, `**` also matches `/`, `?`
+/// matches one character and `[...]`/`[!...]` are character classes.
+pub fn glob_to_regex(pattern: &str) -> String {
+    let mut re = String::from("^");
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '*' => {
+                if chars.get(i + 1) == Some(&'*') {
+                    re.push_str(".*");
+                    i += 1;
+                } else {
+                    re.push_str("[^/]*");
+                }
+            }
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                let close = chars[i + 1..].iter().position(|x| *x == ']').map(|p| p + i + 1);
+                match close {
+                    Some(close) if close > i + 1 => {
+                        re.push('[');
+                        let mut j = i + 1;
+                        if chars[j] == '!' || chars[j] == '^' {
+                            re.push('^');
+                            j += 1;
+                        }
+                        for c in &chars[j..close] {
+                            if *c == '\\' || *c == '[' {
+                                re.push('\\');
+                            }
+                            re.push(*c);
+                        }
+                        re.push(']');
+                        i = close;
+                    }
+                    _ => re.push_str("\\["),
+                }
+            }
+            _ => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    re.push('$');
+    re
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matching() {
+        let g = Glob::new("*.rs").unwrap();
+        assert!(g.is_match("main.rs"));
+        assert!(!g.is_match("main.rs.bak"));
+        assert!(!g.is_match("src/main.rs"));
+        assert!(Glob::new("**/*.rs").unwrap().is_match("src/main.rs"));
+        assert!(Glob::new("file?.[ch]").unwrap().is_match("file1.c"));
+        assert!(!Glob::new("file?.[!ch]").unwrap().is_match("file1.c"));
+        assert!(Glob::with_case("*.JPG", false).unwrap().is_match("a.jpg"));
+        assert!(Glob::new("a+b(1).txt").unwrap().is_match("a+b(1).txt"));
+    }
+}
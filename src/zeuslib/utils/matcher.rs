@@ -0,0 +1,114 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::zeuslib::utils::glob::glob_to_regex;
+
+/// How a pattern typed by the user is interpreted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatchKind {
+    /// The pattern is found anywhere in the name as it is.
+    Substring,
+    /// The whole name matches a shell-style wildcard pattern.
+    Glob,
+    Regex,
+}
+
+impl MatchKind {
+    /// Picks the kind from the pattern itself: `re:` starts a regex, a
+    /// pattern with wildcards is a glob and anything else a substring.
+    /// Returns the kind and the pattern without its prefix.
+    pub fn detect(pattern: &str) -> (Self, &str) {
+        if let Some(re) = pattern.strip_prefix("re:") {
+            (MatchKind::Regex, re)
+        } else if pattern.contains(['*', '?', '[']) {
+            (MatchKind::Glob, pattern)
+        } else {
+            (MatchKind::Substring, pattern)
+        }
+    }
+}
+
+/// Whether to ignore case for `pattern`: with smart-case, a pattern with an
+/// upper-case letter is matched case-sensitively.
+pub fn ignores_case(pattern: &str, ignore_case: bool, smart_case: bool) -> bool {
+    ignore_case && !(smart_case && pattern.chars().any(|c| c.is_uppercase()))
+}
+
+/// A pattern matched against file names, which can also tell where in a name
+/// it matches, for highlighting.
+#[derive(Clone, Debug)]
+pub struct Matcher {
+    pattern: String,
+    re: Regex,
+}
+
+impl Matcher {
+    pub fn new(pattern: &str, kind: MatchKind, ignore_case: bool) -> Result<Self, String> {
+        let re = match kind {
+            MatchKind::Substring => regex::escape(pattern),
+            MatchKind::Glob => glob_to_regex(pattern),
+            MatchKind::Regex => String::from(pattern),
+        };
+        let re = RegexBuilder::new(&re)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            pattern: String::from(pattern),
+            re,
+        })
+    }
+
+    /// The pattern as the user typed it.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Changes what `as_str` returns, e.g. to include a prefix that chose
+    /// the kind.
+    pub fn set_label(&mut self, pattern: &str) {
+        self.pattern = String::from(pattern);
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.re.is_match(name)
+    }
+
+    /// Byte ranges of the non-empty matches in `name`.
+    pub fn find_ranges(&self, name: &str) -> Vec<Range<usize>> {
+        self.re
+            .find_iter(name)
+            .filter(|m| m.start() < m.end())
+            .map(|m| m.range())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smart_case_and_kinds() {
+        assert!(ignores_case("readme", true, true));
+        assert!(!ignores_case("README", true, true));
+        assert!(ignores_case("README", true, false));
+        assert!(!ignores_case("readme", false, true));
+
+        let m = Matcher::new("a.b", MatchKind::Substring, true).unwrap();
+        assert!(m.is_match("xA.Bx"));
+        assert!(!m.is_match("axb"));
+        let m = Matcher::new("a.b", MatchKind::Regex, false).unwrap();
+        assert!(m.is_match("axb"));
+        assert_eq!(m.find_ranges("axb ayb"), vec![0..3, 4..7]);
+        assert!(Matcher::new("(", MatchKind::Regex, false).is_err());
+
+        assert_eq!(MatchKind::detect("re:^a"), (MatchKind::Regex, "^a"));
+        assert_eq!(MatchKind::detect("*.rs"), (MatchKind::Glob, "*.rs"));
+        assert_eq!(MatchKind::detect("main"), (MatchKind::Substring, "main"));
+        let m = Matcher::new("*.RS", MatchKind::Glob, true).unwrap();
+        assert!(m.is_match("main.rs"));
+        assert!(!m.is_match("main.rs.bak"));
+    }
+}
@@ -0,0 +1,131 @@
+/// Splits a command line into words the way a POSIX shell would for simple
+/// cases: whitespace separates words, single quotes keep everything literal,
+/// double quotes keep whitespace and backslash escapes the next character.
+pub fn split(line: &str) -> Result<Vec<String>, String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut cur = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => cur.push(c),
+                        None => return Err(String::from("Unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => cur.push(c),
+                            None => return Err(String::from("Unterminated double quote")),
+                        },
+                        Some(c) => cur.push(c),
+                        None => return Err(String::from("Unterminated double quote")),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    cur.push(c);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut cur));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                cur.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(cur);
+    }
+    Ok(words)
+}
+
+/// Expands `$NAME`, `${NAME}` and `${NAME:-default}` with environment
+/// variables. Unset variables expand to nothing.
+pub fn expand_vars(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c != '$' || i + 1 >= chars.len() {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        if chars[i + 1] == '{' {
+            // Find the matching brace, defaults may nest
+            let mut depth = 0;
+            let mut end = None;
+            for (j, c) in chars.iter().enumerate().skip(i + 1) {
+                match c {
+                    '{' => depth += 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = Some(j);
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(end) = end {
+                let inner: String = chars[i + 2..end].iter().collect();
+                let (name, default) = match inner.find(":-") {
+                    Some(p) => (&inner[..p], Some(&inner[p + 2..])),
+                    None => (inner.as_str(), None),
+                };
+                match (std::env::var(name), default) {
+                    (Ok(v), _) if !v.is_empty() => out.push_str(&v),
+                    (_, Some(default)) => out.push_str(&expand_vars(default)),
+                    _ => {}
+                }
+                i = end + 1;
+                continue;
+            }
+        } else if chars[i + 1] == '_' || chars[i + 1].is_ascii_alphabetic() {
+            let mut j = i + 1;
+            while j < chars.len() && (chars[j] == '_' || chars[j].is_ascii_alphanumeric()) {
+                j += 1;
+            }
+            let name: String = chars[i + 1..j].iter().collect();
+            out.push_str(&std::env::var(name).unwrap_or_default());
+            i = j;
+            continue;
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_words() {
+        assert_eq!(split("a  b\tc").unwrap(), vec!["a", "b", "c"]);
+        assert_eq!(split("cd 'my dir'").unwrap(), vec!["cd", "my dir"]);
+        assert_eq!(split(r#"x "a \"b\"" c\ d"#).unwrap(), vec!["x", "a \"b\"", "c d"]);
+        assert_eq!(split("e ''").unwrap(), vec!["e", ""]);
+        assert!(split("'oops").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::zeuslib::events::{Event, EventSender};
+
+/// How long the watching thread waits for changes before checking whether
+/// the watcher has been dropped.
+const POLL_MS: libc::c_int = 250;
+
+/// Changes to a directory that make its listing stale, and the directory
+/// itself going away.
+const WATCH_MASK: u32 = libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_ATTRIB
+    | libc::IN_MODIFY
+    | libc::IN_DELETE_SELF
+    | libc::IN_MOVE_SELF;
+
+/// A change seen by the watching thread.
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    /// Entries of the directory were added, removed or changed, or the
+    /// directory itself was removed or moved.
+    Changed(PathBuf),
+    /// The kernel dropped changes, any watched directory may be stale.
+    Overflow,
+}
+
+/// Watched directories by watch descriptor, shared with the watching thread.
+type Watches = Arc<Mutex<HashMap<i32, PathBuf>>>;
+
+/// Watches the directories shown in file lists with inotify, reporting
+/// changes to the event loop, and holds them back until they have settled.
+#[derive(Default)]
+pub struct Watcher {
+    /// The inotify instance, once a sender is set.
+    fd: Option<RawFd>,
+    watches: Watches,
+    stopped: Arc<AtomicBool>,
+    pub changes: ChangeQueue,
+}
+
+impl Watcher {
+    /// Starts watching on a worker thread that reports to `sender`. Nothing
+    /// is watched before this.
+    pub fn set_sender(&mut self, sender: EventSender) -> io::Result<()> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.fd = Some(fd);
+        let watches = self.watches.clone();
+        let stopped = self.stopped.clone();
+        thread::spawn(move || {
+            read_events(fd, &watches, &stopped, &sender);
+            unsafe {
+                libc::close(fd);
+            }
+        });
+        Ok(())
+    }
+
+    /// Watches exactly `dirs`: new ones are added and those no longer in it
+    /// dropped. A directory that cannot be watched, e.g. because it is gone,
+    /// is tried again on the next call.
+    pub fn watch_dirs(&mut self, dirs: &HashSet<PathBuf>) {
+        let fd = match self.fd {
+            Some(fd) => fd,
+            None => return,
+        };
+        let mut watches = self.watches.lock().unwrap();
+        watches.retain(|wd, dir| {
+            let keep = dirs.contains(dir);
+            if !keep {
+                unsafe {
+                    libc::inotify_rm_watch(fd, *wd);
+                }
+            }
+            keep
+        });
+        let watched: HashSet<PathBuf> = watches.values().cloned().collect();
+        for dir in dirs.difference(&watched) {
+            let path = match CString::new(dir.as_os_str().as_bytes()) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            let wd = unsafe { libc::inotify_add_watch(fd, path.as_ptr(), WATCH_MASK) };
+            if wd >= 0 {
+                watches.insert(wd, dir.clone());
+            }
+        }
+    }
+
+    /// The directories being watched.
+    pub fn watched(&self) -> Vec<PathBuf> {
+        self.watches.lock().unwrap().values().cloned().collect()
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Reads changes from the inotify instance `fd` until `stopped` is set or
+/// the event loop is gone, sending each changed directory once per read.
+fn read_events(fd: RawFd, watches: &Watches, stopped: &AtomicBool, sender: &EventSender) {
+    // Room for many events at once, aligned for `inotify_event`
+    let mut buf = vec![0u64; 1024];
+    let size = mem::size_of::<libc::inotify_event>();
+    while !stopped.load(Ordering::Relaxed) {
+        let mut fds = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fds, 1, POLL_MS) } <= 0 {
+            continue;
+        }
+        let len = buf.len() * mem::size_of::<u64>();
+        let n = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, len) };
+        if n < 0 {
+            match io::Error::last_os_error().kind() {
+                io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock => continue,
+                _ => return,
+            }
+        }
+        let bytes = buf.as_ptr() as *const u8;
+        let mut changed: HashSet<PathBuf> = HashSet::new();
+        let mut overflow = false;
+        let mut offset = 0;
+        while offset + size <= n as usize {
+            let evt: libc::inotify_event = unsafe {
+                std::ptr::read_unaligned(bytes.add(offset) as *const libc::inotify_event)
+            };
+            // The name of the entry, if any, follows the event
+            offset += size + evt.len as usize;
+            if evt.mask & libc::IN_Q_OVERFLOW != 0 {
+                overflow = true;
+                continue;
+            }
+            let mut watches = watches.lock().unwrap();
+            if evt.mask & libc::IN_IGNORED != 0 {
+                // The watch is gone, e.g. with its directory
+                watches.remove(&evt.wd);
+            } else if evt.mask & libc::IN_MOVE_SELF != 0 {
+                // The watch follows the directory, which no longer has the
+                // path it is known by; `watch_dirs` watches that path again
+                if let Some(dir) = watches.remove(&evt.wd) {
+                    unsafe {
+                        libc::inotify_rm_watch(fd, evt.wd);
+                    }
+                    changed.insert(dir);
+                }
+            } else if let Some(dir) = watches.get(&evt.wd) {
+                changed.insert(dir.clone());
+            }
+        }
+        let mut events: Vec<WatchEvent> = changed.into_iter().map(WatchEvent::Changed).collect();
+        if overflow {
+            events.push(WatchEvent::Overflow);
+        }
+        for evt in events {
+            if sender.send(Event::Watch(evt)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// How many times the delay a directory that keeps changing is held back
+/// at most, so that one written to constantly is still refreshed.
+const MAX_HOLD: u32 = 4;
+
+/// Directories reported changed, held back until they have been quiet for a
+/// while so that a burst of changes refreshes a listing once.
+#[derive(Debug, Default)]
+pub struct ChangeQueue {
+    /// When each directory first and last changed.
+    dirs: HashMap<PathBuf, (Instant, Instant)>,
+}
+
+impl ChangeQueue {
+    pub fn push(&mut self, dir: PathBuf, now: Instant) {
+        self.dirs.entry(dir).or_insert((now, now)).1 = now;
+    }
+
+    /// Takes the directories that have not changed for `delay`, or have
+    /// been held back for `MAX_HOLD` times as long.
+    pub fn take_due(&mut self, delay: Duration, now: Instant) -> Vec<PathBuf> {
+        let due: Vec<PathBuf> = self
+            .dirs
+            .iter()
+            .filter(|(_, (first, last))| {
+                now.duration_since(*last) >= delay || now.duration_since(*first) >= delay * MAX_HOLD
+            })
+            .map(|(dir, _)| dir.clone())
+            .collect();
+        for dir in due.iter() {
+            self.dirs.remove(dir);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zeuslib::utils::testing::TempDir;
+
+    #[test]
+    fn changes_wait_until_quiet() {
+        let delay = Duration::from_millis(100);
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut queue = ChangeQueue::default();
+        queue.push(PathBuf::from("/a"), start);
+        queue.push(PathBuf::from("/b"), start);
+        assert!(queue.take_due(delay, start + ms(50)).is_empty());
+        queue.push(PathBuf::from("/a"), start + ms(60));
+        assert_eq!(queue.take_due(delay, start + ms(120)), vec![PathBuf::from("/b")]);
+        // Changing all the time only delays a refresh so long
+        for t in (100..400).step_by(50) {
+            queue.push(PathBuf::from("/a"), start + ms(t));
+        }
+        assert_eq!(queue.take_due(delay, start + ms(400)), vec![PathBuf::from("/a")]);
+        assert!(queue.take_due(delay, start + ms(1000)).is_empty());
+    }
+
+    #[test]
+    fn changes_are_reported() {
+        let tmp = TempDir::new("watch");
+        let dir = tmp.path().to_path_buf();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = Watcher::default();
+        watcher.set_sender(tx).unwrap();
+        watcher.watch_dirs(&std::iter::once(dir.clone()).collect());
+        assert_eq!(watcher.watched(), vec![dir.clone()]);
+        std::fs::write(dir.join("new"), "").unwrap();
+        match rx.recv_timeout(Duration::from_secs(5)) {
+            Ok(Event::Watch(WatchEvent::Changed(changed))) => assert_eq!(changed, dir),
+            _ => panic!("no change reported"),
+        }
+        // The directory going away is a change too, and ends its watch
+        let moved = tmp.path().with_extension("moved");
+        std::fs::rename(&dir, &moved).unwrap();
+        let reported = (0..5).any(|_| {
+            matches!(
+                rx.recv_timeout(Duration::from_secs(5)),
+                Ok(Event::Watch(WatchEvent::Changed(changed))) if changed == dir
+            ) && watcher.watched().is_empty()
+        });
+        std::fs::rename(&moved, &dir).unwrap();
+        assert!(reported, "move not reported");
+        watcher.watch_dirs(&HashSet::new());
+        assert!(watcher.watched().is_empty());
+    }
+}